@@ -0,0 +1,289 @@
+//! The analytics engine, consuming chat events independently of the UI. Its
+//! state is observed by the GUI and the headless collector alike.
+
+use std::time::{Duration, Instant};
+
+use im::Vector;
+
+use crate::{
+	chat::{Ban, ChatEvent, ClearMessage, Message, UserNotice},
+	giveaway::Giveaway,
+	highlights::{self, Highlight, SpikeDetector},
+	metrics::ChatMetrics,
+	session::Sessions,
+	settings::Settings,
+	terms::TermFilter,
+};
+
+/// The analytics engine and its state.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "gui", derive(druid::Data, druid::Lens))]
+pub struct Analytics {
+	/// Metrics of the running session.
+	pub metrics: ChatMetrics,
+	/// The giveaway.
+	pub giveaway: Giveaway,
+	/// The running and past sessions.
+	pub sessions: Sessions,
+	/// Spikes of chat activity in the running session, oldest first.
+	pub highlights: Vector<Highlight>,
+	/// Chat inactivity after which a session is stopped. Sessions are started
+	/// automatically by chat activity, unless this is zero.
+	#[cfg_attr(feature = "gui", data(ignore))]
+	session_timeout: Duration,
+	/// Time the last session was stopped by hand, if it was. Sessions are not
+	/// started automatically again before the session timeout passed.
+	#[cfg_attr(feature = "gui", data(ignore))]
+	stopped_manually: Option<Instant>,
+	/// Filter extracting the terms of the messages.
+	#[cfg_attr(feature = "gui", data(ignore))]
+	term_filter: TermFilter,
+	/// Detector of the spikes of chat activity.
+	#[cfg_attr(feature = "gui", data(ignore))]
+	spike_detector: SpikeDetector,
+}
+
+impl Analytics {
+	/// Apply new settings, which finishes the running session and resets the
+	/// state.
+	pub fn apply_settings(&mut self, settings: &Settings) {
+		self.stop_session(None);
+		self.stopped_manually = None;
+		self.metrics.reset();
+		self.highlights.clear();
+		self.giveaway.apply_settings(settings);
+		self.session_timeout = Duration::from_secs(60 * settings.session_timeout);
+		self.term_filter = TermFilter::from_settings(settings);
+		self.spike_detector = SpikeDetector::from_settings(settings);
+	}
+
+	/// Handle any chat event.
+	pub fn handle_event(&mut self, event: &ChatEvent) {
+		match event {
+			ChatEvent::Message(message) => self.handle_message(message),
+			ChatEvent::ClearMessage(message) => self.handle_clear_message(message),
+			ChatEvent::UserNotice(notice) => self.handle_user_notice(notice),
+			ChatEvent::Ban(ban) => self.handle_ban(ban),
+		}
+	}
+
+	/// Handle a new chat message.
+	pub fn handle_message(&mut self, message: &Message) {
+		// Start a session automatically when chat becomes active, but not right
+		// after a session was stopped by hand.
+		if self.sessions.running_since.is_none()
+			&& !self.session_timeout.is_zero()
+			&& self
+				.stopped_manually
+				.map_or(true, |stopped| stopped.elapsed() > self.session_timeout)
+		{
+			self.start_session(message.timestamp);
+		}
+		self.sessions.activity(message.timestamp);
+
+		let last_minute = self
+			.metrics
+			.channels
+			.get(&message.channel)
+			.and_then(|metrics| metrics.series.buckets().back())
+			.map(|bucket| bucket.minute);
+		self.metrics.add_message(message);
+		self.metrics.add_terms(message, &self.term_filter.terms(message));
+		let chatter = self
+			.metrics
+			.channels
+			.get(&message.channel)
+			.and_then(|metrics| metrics.chatters.get(&message.author.id));
+		self.giveaway.handle_message(message, chatter);
+
+		// The last minute is complete once the first message of a newer one
+		// arrives.
+		if let Some(minute) =
+			last_minute.filter(|minute| *minute < message.timestamp.div_euclid(60))
+		{
+			self.detect_spike(&message.channel, minute);
+		}
+	}
+
+	/// Record a highlight if the minute was a spike of chat activity in the
+	/// channel.
+	fn detect_spike(&mut self, channel: &str, minute: i64) {
+		let highlight = self
+			.metrics
+			.channels
+			.get(channel)
+			.and_then(|metrics| self.spike_detector.detect(channel, metrics, minute));
+		if let Some(mut highlight) = highlight {
+			highlight.offset =
+				self.sessions.running_since.map(|started| (highlight.started - started).max(0));
+			highlights::add(&mut self.highlights, highlight);
+		}
+	}
+
+	/// Handle a cleared chat message.
+	pub fn handle_clear_message(&mut self, message: &ClearMessage) {
+		self.metrics.add_clear_message(message);
+	}
+
+	/// Handle a user notice.
+	pub fn handle_user_notice(&mut self, notice: &UserNotice) {
+		self.metrics.add_user_notice(notice);
+	}
+
+	/// Handle a ban or timeout.
+	pub fn handle_ban(&mut self, ban: &Ban) {
+		self.metrics.add_ban(ban);
+		self.giveaway.handle_ban(ban);
+	}
+
+	/// Stop the session if chat was inactive for longer than the session
+	/// timeout. To be called periodically.
+	pub fn check_inactivity(&mut self, now: Instant) {
+		if !self.session_timeout.is_zero()
+			&& self.sessions.inactive_since(now, self.session_timeout)
+		{
+			self.stop_session(None);
+		}
+	}
+
+	/// Start a new session at the timestamp, finishing the running one and
+	/// resetting the metrics.
+	pub fn start_session(&mut self, timestamp: i64) {
+		self.stop_session(None);
+		self.stopped_manually = None;
+		self.metrics.reset();
+		self.highlights.clear();
+		self.sessions.start(timestamp);
+	}
+
+	/// Stop the running session by hand at the timestamp. Chat activity does
+	/// not start a new session before the session timeout passed.
+	pub fn stop_session_manually(&mut self, ended: i64) {
+		self.stop_session(Some(ended));
+		self.stopped_manually = Some(Instant::now());
+	}
+
+	/// Stop the running session, if any, at the timestamp or the last chat
+	/// activity if not given, and store it with the current metrics.
+	pub fn stop_session(&mut self, ended: Option<i64>) {
+		let mut channels = self.metrics.channels.keys().cloned().collect::<Vec<_>>();
+		channels.sort();
+		self.sessions.finish(ended, channels.join(", "), self.metrics.summary());
+	}
+}
+
+/// Tests of the analytics engine.
+#[cfg(test)]
+mod tests {
+	use std::time::{Duration, Instant};
+
+	use super::Analytics;
+	use crate::{chat::Message, giveaway::Giveaway, session::Sessions, settings::Settings};
+
+	/// Analytics with automatic sessions, storing neither the sessions nor the
+	/// giveaway winners in files.
+	fn analytics() -> Analytics {
+		let mut analytics = Analytics {
+			sessions: Sessions::in_memory(),
+			giveaway: Giveaway::in_memory(),
+			..Analytics::default()
+		};
+		analytics.apply_settings(&Settings::default());
+		analytics
+	}
+
+	/// The first message starts a session, and a new session starts with new
+	/// metrics.
+	#[test]
+	fn message_starts_session_and_resets_metrics() {
+		let mut analytics = analytics();
+		analytics.handle_message(&Message::test("a", "Alice", "hello", 1000));
+		analytics.handle_message(&Message::test("a", "Bob", "hi", 1010));
+		assert_eq!(analytics.sessions.running_since, Some(1000));
+		assert_eq!(analytics.metrics.all.total_messages, 2);
+
+		analytics.stop_session(None);
+		assert_eq!(analytics.sessions.running_since, None);
+		assert_eq!(analytics.sessions.past.len(), 1);
+		assert_eq!(analytics.sessions.past[0].ended, 1010);
+		assert_eq!(analytics.sessions.past[0].totals.total_messages, 2);
+
+		analytics.handle_message(&Message::test("a", "Carol", "back again", 5000));
+		assert_eq!(analytics.sessions.running_since, Some(5000));
+		assert_eq!(analytics.metrics.all.total_messages, 1);
+		assert_eq!(analytics.metrics.all.chatters.len(), 1);
+	}
+
+	/// Messages right after a session was stopped by hand do not start a new
+	/// one, but starting one by hand does.
+	#[test]
+	fn manual_stop_prevents_automatic_sessions() {
+		let mut analytics = analytics();
+		analytics.handle_message(&Message::test("a", "Alice", "hello", 1000));
+		analytics.stop_session_manually(1010);
+		assert_eq!(analytics.sessions.past.len(), 1);
+		assert_eq!(analytics.sessions.past[0].ended, 1010);
+
+		analytics.handle_message(&Message::test("a", "Bob", "still here", 1020));
+		assert_eq!(analytics.sessions.running_since, None);
+		assert_eq!(analytics.sessions.past.len(), 1);
+		assert_eq!(analytics.metrics.all.total_messages, 2);
+
+		analytics.start_session(1030);
+		analytics.handle_message(&Message::test("a", "Bob", "new session", 1040));
+		assert_eq!(analytics.sessions.running_since, Some(1030));
+		assert_eq!(analytics.metrics.all.total_messages, 1);
+	}
+
+	/// Sessions are stopped after the timeout without chat activity only.
+	#[test]
+	fn inactivity_stops_session() {
+		let mut analytics = analytics();
+		let timeout = Duration::from_secs(60 * Settings::default().session_timeout);
+		analytics.handle_message(&Message::test("a", "Alice", "hello", 1000));
+
+		analytics.check_inactivity(Instant::now() + timeout / 2);
+		assert_eq!(analytics.sessions.running_since, Some(1000));
+
+		analytics.check_inactivity(Instant::now() + timeout + Duration::from_secs(1));
+		assert_eq!(analytics.sessions.running_since, None);
+		assert_eq!(analytics.sessions.past.len(), 1);
+		assert_eq!(analytics.sessions.past[0].channels, "a");
+	}
+
+	/// Without a session timeout, no sessions are started automatically.
+	#[test]
+	fn no_automatic_sessions_without_timeout() {
+		let mut analytics = Analytics {
+			sessions: Sessions::in_memory(),
+			giveaway: Giveaway::in_memory(),
+			..Analytics::default()
+		};
+		analytics.apply_settings(&Settings { session_timeout: 0, ..Settings::default() });
+		analytics.handle_message(&Message::test("a", "Alice", "hello", 1000));
+		analytics.check_inactivity(Instant::now() + Duration::from_secs(24 * 60 * 60));
+		assert_eq!(analytics.sessions.running_since, None);
+		assert_eq!(analytics.metrics.all.total_messages, 1);
+	}
+
+	/// New settings finish the session and reset the metrics and the giveaway.
+	#[test]
+	fn settings_reset_state() {
+		let mut analytics = analytics();
+		analytics.giveaway.message_start = "!enter".to_owned();
+		analytics.giveaway.open(1000);
+		analytics.handle_message(&Message::test("a", "Alice", "!enter", 1000));
+		analytics.handle_message(&Message::test("b", "Bob", "hello", 1010));
+		assert_eq!(analytics.giveaway.people_entered.len(), 1);
+
+		analytics.apply_settings(&Settings::default());
+		assert_eq!(analytics.sessions.running_since, None);
+		assert_eq!(analytics.sessions.past.len(), 1);
+		assert_eq!(analytics.sessions.past[0].channels, "a, b");
+		assert_eq!(analytics.metrics.all.total_messages, 0);
+		assert!(analytics.metrics.channels.is_empty());
+		assert!(analytics.highlights.is_empty());
+		assert!(analytics.giveaway.people_entered.is_empty());
+		assert!(!analytics.giveaway.open);
+	}
+}
@@ -0,0 +1,230 @@
+//! Headless binary collecting the chat metrics without the GUI, e.g. on a
+//! server. It uses the same settings file as the GUI, logs a summary
+//! periodically and writes the current metrics to a file.
+
+use std::{
+	collections::BTreeMap,
+	fs::File,
+	path::{Path, PathBuf},
+	time::Duration,
+};
+
+use color_eyre::{
+	eyre::{bail, eyre, WrapErr},
+	Result,
+};
+use serde::Serialize;
+use tokio::{
+	sync::{mpsc, oneshot},
+	time::{self, Instant},
+};
+use twitch_chat_metrics::{
+	analytics::Analytics,
+	chat::ChatReceiver,
+	highlights::Highlight,
+	history,
+	metrics::{ChatterOrder, ChatterStats, EmoteOrder, EmoteStats, MetricsSummary},
+	session,
+	settings::Settings,
+	source::{self, SourceEvent},
+	terms::TrendingTerm,
+};
+
+/// Command line options.
+#[derive(Debug)]
+struct Options {
+	/// Interval to log summaries and write the results in.
+	interval: Duration,
+	/// File to write the results to.
+	output: PathBuf,
+}
+
+impl Options {
+	/// Default interval in seconds.
+	const DEFAULT_INTERVAL: u64 = 60;
+	/// Default results file.
+	const DEFAULT_OUTPUT: &'static str = "metrics.json";
+
+	/// Parse the options from the command line arguments.
+	fn from_args() -> Result<Self> {
+		let mut options = Self {
+			interval: Duration::from_secs(Self::DEFAULT_INTERVAL),
+			output: Self::DEFAULT_OUTPUT.into(),
+		};
+
+		let mut args = std::env::args().skip(1);
+		while let Some(arg) = args.next() {
+			let mut value = || args.next().ok_or_else(|| eyre!("Missing value for {arg}"));
+			match arg.as_str() {
+				"--interval" => {
+					let seconds: u64 = value()?.parse().wrap_err("Invalid interval")?;
+					options.interval = Duration::from_secs(seconds.max(1));
+				}
+				"--output" => options.output = value()?.into(),
+				_ => bail!(
+					"Unknown argument {arg}\nUsage: twitch-chat-metrics-headless [--interval \
+					 <seconds>] [--output <file>]"
+				),
+			}
+		}
+
+		Ok(options)
+	}
+}
+
+/// Results written to the output file.
+#[derive(Debug, Serialize)]
+struct Results {
+	/// Timestamp when the running session started, if any.
+	session_started: Option<i64>,
+	/// Timestamp of the results.
+	updated: i64,
+	/// Metrics of all channels combined.
+	totals: MetricsSummary,
+	/// Metrics per channel.
+	channels: BTreeMap<String, MetricsSummary>,
+	/// Top chatters of all channels by messages.
+	top_chatters: Vec<ChatterStats>,
+	/// Most used emotes of all channels.
+	top_emotes: Vec<EmoteStats>,
+	/// Least used emotes of all channels.
+	least_used_emotes: Vec<EmoteStats>,
+	/// Terms trending in the last minutes in all channels.
+	trending_terms: Vec<TrendingTerm>,
+	/// Spikes of chat activity in the running session.
+	highlights: Vec<Highlight>,
+}
+
+impl Results {
+	/// Gather the results of the metrics.
+	fn new(analytics: &Analytics) -> Self {
+		let metrics = &analytics.metrics;
+		Self {
+			session_started: analytics.sessions.running_since,
+			updated: session::now(),
+			totals: metrics.summary(),
+			channels: metrics
+				.channels
+				.iter()
+				.map(|(channel, metrics)| (channel.clone(), metrics.summary()))
+				.collect(),
+			top_chatters: metrics
+				.all
+				.top_chatters(ChatterOrder::Messages)
+				.iter()
+				.cloned()
+				.collect(),
+			top_emotes: metrics.all.top_emotes(EmoteOrder::MostUsed).iter().cloned().collect(),
+			least_used_emotes: metrics
+				.all
+				.top_emotes(EmoteOrder::LeastUsed)
+				.iter()
+				.cloned()
+				.collect(),
+			trending_terms: metrics.all.trending_terms().iter().cloned().collect(),
+			highlights: analytics.highlights.iter().cloned().collect(),
+		}
+	}
+
+	/// Log a summary of the results.
+	fn log(&self) {
+		let totals = &self.totals;
+		tracing::info!(
+			"{} messages by {} chatters ({} new, {} returning), {} bits, {} new subs, {} resubs, \
+			 {} gifted subs, {} raids with {} viewers, {} announcements, {} messages deleted, {} \
+			 bans, {} timeouts",
+			totals.total_messages,
+			totals.unique_chatters,
+			totals.new_chatters,
+			totals.returning_chatters,
+			totals.total_bits,
+			totals.new_subs,
+			totals.resubs,
+			totals.gifted_subs,
+			totals.raids,
+			totals.raid_viewers,
+			totals.announcements,
+			totals.messages_cleared,
+			totals.bans,
+			totals.timeouts,
+		);
+	}
+
+	/// Write the results to the file.
+	fn write(&self, path: &Path) -> Result<()> {
+		let file = File::create(path)?;
+		serde_json::to_writer_pretty(file, self)?;
+		Ok(())
+	}
+
+	/// Log and write the results, logging errors only.
+	fn report(&self, path: &Path) {
+		self.log();
+		if let Err(err) = self.write(path) {
+			tracing::error!("Writing results to {} failed: {err}", path.display());
+		}
+	}
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+	color_eyre::install()?;
+	tracing_subscriber::fmt::init();
+
+	let options = Options::from_args()?;
+	let settings = Settings::from_file().wrap_err("Loading the settings failed")?;
+	if settings.channels().is_empty() && settings.replay().is_none() {
+		bail!("No twitch channels configured in the settings");
+	}
+
+	let (history, known_chatters) = history::from_settings(&settings);
+	let (sender, mut events) = mpsc::unbounded_channel();
+	let (stop_trigger, stop_trigger_receiver) = oneshot::channel();
+	let receiver = ChatReceiver::builder()
+		.source(source::from_settings(&settings))
+		.sink(Box::new(sender))
+		.history(history)
+		.known_chatters(known_chatters)
+		.stop_trigger(stop_trigger_receiver)
+		.build()
+		.spawn();
+
+	let mut analytics = Analytics::default();
+	analytics.apply_settings(&settings);
+	analytics.sessions.load().ok();
+	if settings.session_timeout == 0 {
+		// Without automatic sessions, the whole run is one session.
+		analytics.start_session(session::now());
+	}
+
+	let mut reports = time::interval_at(Instant::now() + options.interval, options.interval);
+	loop {
+		tokio::select! {
+			event = events.recv() => match event {
+				Some(SourceEvent::Chat(event)) => analytics.handle_event(&event),
+				Some(SourceEvent::Status(status)) => tracing::info!("{status}"),
+				// The source is exhausted, e.g. a replay finished.
+				None => break,
+			},
+			now = reports.tick() => {
+				analytics.check_inactivity(now.into_std());
+				analytics.metrics.update_trending_terms();
+				Results::new(&analytics).report(&options.output);
+			}
+			result = tokio::signal::ctrl_c() => {
+				result?;
+				break;
+			}
+		}
+	}
+
+	stop_trigger.send(()).ok();
+	// Wait until the remaining events are written to the chat history.
+	if receiver.join().is_err() {
+		tracing::error!("Chat receiver thread panicked");
+	}
+	analytics.metrics.update_trending_terms();
+	Results::new(&analytics).report(&options.output);
+	analytics.stop_session(Some(session::now()));
+	Ok(())
+}
@@ -1,140 +1,454 @@
 //! Chat handler part of the app.
 
+#[cfg(feature = "gui")]
+use std::sync::Arc;
 use std::{
 	fmt::Display,
-	sync::Arc,
 	thread::{self, JoinHandle},
+	time::Duration,
 };
 
+#[cfg(feature = "gui")]
 use druid::{Data, ExtEventSink, Selector, Target};
-use tokio::sync::oneshot::{self, error::TryRecvError};
-use twitch_irc::{
-	login::StaticLoginCredentials,
-	message::{ClearMsgMessage, Emote, PrivmsgMessage, ServerMessage, TwitchUserBasics},
-	ClientConfig, SecureTCPTransport, TwitchIRCClient,
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot};
+use twitch_irc::message::{
+	Badge, ClearChatAction, ClearChatMessage, ClearMsgMessage, Emote, PrivmsgMessage,
+	ServerMessage, TwitchUserBasics, UserNoticeEvent as TwitchUserNoticeEvent, UserNoticeMessage,
 };
 use typed_builder::TypedBuilder;
 
+#[cfg(feature = "gui")]
 use crate::ui::UIState;
+use crate::{
+	history::{HistoryWriter, KnownChatters},
+	source::{ChatSource, SourceEvent},
+	spam::{SpamDetector, SpamKind},
+};
 
 /// Selector string for new chat messages' commands.
+#[cfg(feature = "gui")]
 pub const NEW_CHAT_MESSAGE: Selector<Arc<Message>> = Selector::new("NEW_CHAT_MESSAGE");
 /// Selector string for cleared chat messages' commands.
+#[cfg(feature = "gui")]
 pub const CLEAR_CHAT_MESSAGE: Selector<Arc<ClearMessage>> = Selector::new("CLEAR_CHAT_MESSAGE");
+/// Selector string for user notices' commands.
+#[cfg(feature = "gui")]
+pub const NEW_USER_NOTICE: Selector<Arc<UserNotice>> = Selector::new("NEW_USER_NOTICE");
+/// Selector string for banned or timed out users' commands.
+#[cfg(feature = "gui")]
+pub const USER_BANNED: Selector<Arc<Ban>> = Selector::new("USER_BANNED");
+/// Selector string for connection status updates' commands.
+#[cfg(feature = "gui")]
+pub const CONNECTION_STATUS: Selector<ConnectionStatus> = Selector::new("CONNECTION_STATUS");
+
+/// Consumer of the received chat events and connection status updates, like
+/// the UI.
+pub trait ChatSink: Send {
+	/// Handle a chat event.
+	fn chat_event(&self, event: ChatEvent);
+	/// Handle a connection status update.
+	fn status(&self, status: ConnectionStatus);
+}
 
 /// Chat receiver spawner.
 #[derive(TypedBuilder)]
 pub struct ChatReceiver {
-	/// The twitch channel to join to.
-	channel: String,
-	/// The event sender to send data to the UI.
-	event_sender: ExtEventSink,
+	/// The source of chat events.
+	source: Box<dyn ChatSource>,
+	/// The consumer of the events.
+	sink: Box<dyn ChatSink>,
+	/// Writer to store the events in the chat history, if enabled.
+	history: Option<HistoryWriter>,
+	/// Chatters known from the chat history, to mark returning chatters.
+	#[builder(default)]
+	known_chatters: Option<KnownChatters>,
+	/// Detector flagging spam messages.
+	#[builder(default)]
+	spam_detector: SpamDetector,
 	/// Trigger to stop the client and stop receiving messages.
 	stop_trigger: oneshot::Receiver<()>,
 }
 
 impl ChatReceiver {
-	/// Spawn the receiver in a new thread.
-	pub fn spawn(self) -> JoinHandle<()> {
+	/// Spawn the receiver in a new thread. The thread finishes once the
+	/// remaining events are written to the chat history.
+	pub fn spawn(mut self) -> JoinHandle<()> {
 		thread::spawn(move || {
-			if self.channel.is_empty() {
-				return;
-			}
-
 			let runtime = tokio::runtime::Builder::new_current_thread()
 				.enable_all()
 				.build()
 				.expect("building tokio runtime");
 			runtime.block_on(self.run());
+			if let Some(history) = self.history.take() {
+				history.finish();
+			}
 		})
 	}
 
 	/// Run the receiver
-	async fn run(mut self) {
-		let (mut messages, client) =
-			TwitchIRCClient::<SecureTCPTransport, StaticLoginCredentials>::new(
-				ClientConfig::default(),
-			);
-
-		client.join(self.channel.clone()).expect("joining channel");
-
-		while let Some(message) = messages.recv().await {
-			match self.stop_trigger.try_recv() {
-				Err(TryRecvError::Empty) => {}
-				_ => break,
+	async fn run(&mut self) {
+		loop {
+			let event = tokio::select! {
+				_ = &mut self.stop_trigger => return,
+				event = self.source.next_event() => event,
+			};
+
+			match event {
+				Some(SourceEvent::Chat(mut event)) => {
+					if let ChatEvent::Message(message) = &mut event {
+						if let Some(known_chatters) = &mut self.known_chatters {
+							message.first_chatted = known_chatters.check_and_remember(
+								&message.channel,
+								&message.author.id,
+								message.timestamp,
+							);
+							message.returning = message.first_chatted.is_some();
+						}
+						message.spam = self.spam_detector.detect(message);
+					}
+					if let Some(history) = &self.history {
+						history.write(event.clone());
+					}
+					self.sink.chat_event(event);
+				}
+				Some(SourceEvent::Status(status)) => self.sink.status(status),
+				None => return,
 			}
+		}
+	}
+}
 
-			match message {
-				ServerMessage::Privmsg(priv_msg) => self.receive_priv_msg(priv_msg),
-				ServerMessage::ClearMsg(clear_msg) => self.receive_clear_msg(clear_msg),
-				_ => {}
+/// Sink forwarding the events to the UI.
+#[cfg(feature = "gui")]
+impl ChatSink for ExtEventSink {
+	fn chat_event(&self, event: ChatEvent) {
+		match event {
+			ChatEvent::Message(message) => {
+				let cloned_message = message.clone();
+				self.add_idle_callback(move |data: &mut UIState| {
+					if !data.channels.contains(&cloned_message.channel) {
+						// Replayed recordings may contain other channels.
+						data.channels.push_back(cloned_message.channel.clone());
+					}
+					data.chat.messages.push_front(cloned_message);
+					let buffer = data.settings.chat_buffer + data.chat.scrollback;
+					if data.chat.messages.len() > buffer {
+						data.chat.messages.truncate(buffer);
+					}
+				});
+				self.submit_command(NEW_CHAT_MESSAGE, Arc::new(message), Target::Auto)
+					.expect("sending new message as command");
+			}
+			ChatEvent::ClearMessage(message) => {
+				self.submit_command(CLEAR_CHAT_MESSAGE, Arc::new(message), Target::Auto)
+					.expect("sending clear message as command");
+			}
+			ChatEvent::UserNotice(notice) => {
+				self.submit_command(NEW_USER_NOTICE, Arc::new(notice), Target::Auto)
+					.expect("sending user notice as command");
+			}
+			ChatEvent::Ban(ban) => {
+				let cloned_ban = ban.clone();
+				self.add_idle_callback(move |data: &mut UIState| {
+					for message in data.chat.messages.iter_mut() {
+						if message.channel == cloned_ban.channel
+							&& message.author.id == cloned_ban.user_id
+						{
+							message.moderated = true;
+						}
+					}
+					data.chat.revision += 1;
+				});
+				self.submit_command(USER_BANNED, Arc::new(ban), Target::Auto)
+					.expect("sending ban as command");
 			}
 		}
 	}
 
-	/// Handle a PrivMsg message.
-	fn receive_priv_msg(&self, priv_msg: PrivmsgMessage) {
-		let message = Message::from(priv_msg);
+	fn status(&self, status: ConnectionStatus) {
+		self.submit_command(CONNECTION_STATUS, status, Target::Auto)
+			.expect("sending connection status as command");
+	}
+}
+
+/// Sink forwarding the events through a channel, e.g. to a headless frontend.
+impl ChatSink for mpsc::UnboundedSender<SourceEvent> {
+	fn chat_event(&self, event: ChatEvent) {
+		self.send(SourceEvent::Chat(event)).ok();
+	}
+
+	fn status(&self, status: ConnectionStatus) {
+		self.send(SourceEvent::Status(status)).ok();
+	}
+}
+
+/// Status of the chat connection.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "gui", derive(Data))]
+pub enum ConnectionStatus {
+	/// Not connected, as no channels are configured.
+	#[default]
+	Disconnected,
+	/// Connecting to the chat.
+	Connecting,
+	/// Connected and joined all channels.
+	Joined,
+	/// Connection was lost, reconnecting.
+	Reconnecting {
+		/// Number of the reconnect attempt.
+		attempt: u32,
+	},
+	/// Connection failed permanently.
+	Failed(String),
+	/// Replaying the given recording.
+	Replaying(String),
+	/// Finished replaying the given recording.
+	ReplayFinished(String),
+}
 
-		let cloned_message = message.clone();
-		self.event_sender.add_idle_callback(move |data: &mut UIState| {
-			data.chat.messages.push_front(cloned_message);
-			if data.chat.messages.len() > data.settings.chat_buffer {
-				data.chat.messages.truncate(data.settings.chat_buffer);
+impl Display for ConnectionStatus {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Disconnected => f.write_str("Not connected"),
+			Self::Connecting => f.write_str("Connecting..."),
+			Self::Joined => f.write_str("Connected, joined all channels"),
+			Self::Reconnecting { attempt } => {
+				f.write_fmt(format_args!("Connection lost, reconnecting (attempt {attempt})..."))
 			}
-		});
-		self.event_sender
-			.submit_command(NEW_CHAT_MESSAGE, Arc::new(message), Target::Auto)
-			.expect("sending new message as command");
+			Self::Failed(reason) => f.write_fmt(format_args!("Connection failed: {reason}")),
+			Self::Replaying(file) => f.write_fmt(format_args!("Replaying {file}...")),
+			Self::ReplayFinished(file) => f.write_fmt(format_args!("Finished replaying {file}")),
+		}
+	}
+}
+
+/// Any event in the chat that is stored in the chat history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "event", rename_all = "snake_case")]
+pub enum ChatEvent {
+	/// New chat message.
+	Message(Message),
+	/// Cleared chat message.
+	ClearMessage(ClearMessage),
+	/// User notice.
+	UserNotice(UserNotice),
+	/// Ban or timeout of a user.
+	Ban(Ban),
+}
+
+impl ChatEvent {
+	/// Get the normalized event from a message from the server, if it is of
+	/// interest.
+	#[must_use]
+	pub fn from_server_message(message: ServerMessage) -> Option<Self> {
+		match message {
+			ServerMessage::Privmsg(priv_msg) => Some(Self::Message(priv_msg.into())),
+			ServerMessage::ClearMsg(clear_msg) => Some(Self::ClearMessage(clear_msg.into())),
+			ServerMessage::ClearChat(clear_chat) => Ban::from_clear_chat(clear_chat).map(Self::Ban),
+			ServerMessage::UserNotice(user_notice) => Some(Self::UserNotice(user_notice.into())),
+			_ => None,
+		}
+	}
+
+	/// Name of the kind of event.
+	#[must_use]
+	pub fn kind(&self) -> &'static str {
+		match self {
+			Self::Message(_) => "message",
+			Self::ClearMessage(_) => "clear_message",
+			Self::UserNotice(_) => "user_notice",
+			Self::Ban(_) => "ban",
+		}
+	}
+
+	/// Login name of the channel the event happened in.
+	#[must_use]
+	pub fn channel(&self) -> &str {
+		match self {
+			Self::Message(message) => &message.channel,
+			Self::ClearMessage(message) => &message.channel,
+			Self::UserNotice(notice) => &notice.channel,
+			Self::Ban(ban) => &ban.channel,
+		}
 	}
 
-	/// Handle a ClearMsg message.
-	fn receive_clear_msg(&self, clear_msg: ClearMsgMessage) {
-		let message = ClearMessage::from(clear_msg);
+	/// Timestamp when the event happened.
+	#[must_use]
+	pub fn timestamp(&self) -> i64 {
+		match self {
+			Self::Message(message) => message.timestamp,
+			Self::ClearMessage(message) => message.timestamp,
+			Self::UserNotice(notice) => notice.timestamp,
+			Self::Ban(ban) => ban.timestamp,
+		}
+	}
 
-		self.event_sender
-			.submit_command(CLEAR_CHAT_MESSAGE, Arc::new(message), Target::Auto)
-			.expect("sending clear message as command");
+	/// ID of the user the event is about, if it is known.
+	#[must_use]
+	pub fn user_id(&self) -> Option<&str> {
+		match self {
+			Self::Message(message) => Some(&message.author.id),
+			Self::ClearMessage(_) => None,
+			Self::UserNotice(notice) => Some(&notice.user.id),
+			Self::Ban(ban) => Some(&ban.user_id),
+		}
 	}
 }
 
 /// A message in the chat.
-#[derive(Debug, Clone, PartialEq, Data)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "gui", derive(Data))]
 pub struct Message {
 	/// Unique ID of the message
 	pub id: String,
+	/// Login name of the channel the message was sent in.
+	#[cfg_attr(feature = "gui", data(ignore))]
+	pub channel: String,
 	/// Timestamp when the message was sent.
-	#[data(ignore)]
+	#[cfg_attr(feature = "gui", data(ignore))]
 	pub timestamp: i64,
 	/// Sender of the message.
-	#[data(ignore)]
+	#[cfg_attr(feature = "gui", data(ignore))]
 	pub author: TwitchUserBasics,
 	/// Message text.
-	#[data(ignore)]
+	#[cfg_attr(feature = "gui", data(ignore))]
 	pub message: String,
 	/// List of emotes used.
-	#[data(ignore)]
+	#[cfg_attr(feature = "gui", data(ignore))]
 	pub emotes: Vec<Emote>,
 	/// Number of bits cheered in this message (if any).
-	#[data(ignore)]
+	#[cfg_attr(feature = "gui", data(ignore))]
 	pub bits: Option<u64>,
-	/// Whether the message had a subscriber badge.
-	#[data(ignore)]
+	/// Whether the message had a subscriber or founder badge.
+	#[cfg_attr(feature = "gui", data(ignore))]
 	pub subscriber: bool,
+	/// Badges of the author, e.g. subscriber, vip, moderator or broadcaster.
+	#[serde(default)]
+	#[cfg_attr(feature = "gui", data(ignore))]
+	pub badges: Vec<Badge>,
+	/// Number of months the author is subscribed to the channel, if known.
+	#[serde(default)]
+	#[cfg_attr(feature = "gui", data(ignore))]
+	pub sub_months: Option<u64>,
+	/// Whether this is the author's first message in the channel ever,
+	/// according to twitch.
+	#[serde(default)]
+	#[cfg_attr(feature = "gui", data(ignore))]
+	pub first_message: bool,
+	/// Whether the author chatted in the channel before, according to the
+	/// chat history.
+	#[serde(default)]
+	#[cfg_attr(feature = "gui", data(ignore))]
+	pub returning: bool,
+	/// Timestamp of the author's first message in the channel according to the
+	/// chat history, if they chatted there before.
+	#[serde(default)]
+	#[cfg_attr(feature = "gui", data(ignore))]
+	pub first_chatted: Option<i64>,
+	/// Kind of spam the message was flagged as, if any.
+	#[serde(default)]
+	#[cfg_attr(feature = "gui", data(ignore))]
+	pub spam: Option<SpamKind>,
+	/// Whether the author was banned or timed out after sending the message.
+	#[serde(default)]
+	pub moderated: bool,
 }
 
 impl From<PrivmsgMessage> for Message {
 	fn from(msg: PrivmsgMessage) -> Self {
-		let subscriber = msg.badge_info.iter().any(|info| info.name.as_str() == "subscriber");
+		// The badge info only has details of some badges, e.g. the exact
+		// subscription months, so the badges have to be checked for the roles.
+		let subscriber =
+			msg.badges.iter().any(|badge| badge.name == "subscriber" || badge.name == "founder");
+		let sub_months = msg
+			.badge_info
+			.iter()
+			.find(|info| info.name == "subscriber" || info.name == "founder")
+			.and_then(|info| info.version.parse().ok());
+		let first_message =
+			msg.source.tags.0.get("first-msg").and_then(Option::as_deref) == Some("1");
 
 		Self {
 			id: msg.message_id,
+			channel: msg.channel_login,
 			timestamp: msg.server_timestamp.timestamp(),
 			author: msg.sender,
 			message: msg.message_text,
 			emotes: msg.emotes,
 			bits: msg.bits,
 			subscriber,
+			badges: msg.badges,
+			sub_months,
+			first_message,
+			returning: false,
+			first_chatted: None,
+			spam: None,
+			moderated: false,
+		}
+	}
+}
+
+impl Message {
+	/// Whether the author has the badge with the name.
+	#[must_use]
+	pub fn has_badge(&self, name: &str) -> bool {
+		self.badges.iter().any(|badge| badge.name == name)
+	}
+
+	/// Whether the author is a VIP of the channel.
+	#[must_use]
+	pub fn is_vip(&self) -> bool {
+		self.has_badge("vip")
+	}
+
+	/// Whether the author is a moderator or the broadcaster of the channel.
+	#[must_use]
+	pub fn is_moderator(&self) -> bool {
+		self.has_badge("moderator") || self.has_badge("broadcaster")
+	}
+
+	/// The message text with the emotes replaced by spaces.
+	#[must_use]
+	pub fn text_without_emotes(&self) -> String {
+		self.message
+			.chars()
+			.enumerate()
+			.map(|(i, c)| {
+				if self.emotes.iter().any(|emote| emote.char_range.contains(&i)) {
+					' '
+				} else {
+					c
+				}
+			})
+			.collect()
+	}
+}
+
+#[cfg(test)]
+impl Message {
+	/// Plain message of the user in the channel at the timestamp, for tests.
+	pub(crate) fn test(channel: &str, user: &str, text: &str, timestamp: i64) -> Self {
+		Self {
+			id: format!("{user}-{timestamp}-{text}"),
+			channel: channel.to_owned(),
+			timestamp,
+			author: TwitchUserBasics {
+				id: format!("id-{user}"),
+				login: user.to_lowercase(),
+				name: user.to_owned(),
+			},
+			message: text.to_owned(),
+			emotes: Vec::new(),
+			bits: None,
+			subscriber: false,
+			badges: Vec::new(),
+			sub_months: None,
+			first_message: false,
+			returning: false,
+			first_chatted: None,
+			spam: None,
+			moderated: false,
 		}
 	}
 }
@@ -146,9 +460,14 @@ impl Display for Message {
 }
 
 /// Message to clear a message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClearMessage {
 	/// Message ID of the message to clear.
 	pub id: String,
+	/// Login name of the channel the message was sent in.
+	pub channel: String,
+	/// Timestamp when the message was cleared.
+	pub timestamp: i64,
 	/// Message author's login name of the message.
 	pub author: String,
 	/// Message text of the message to clear.
@@ -159,8 +478,242 @@ impl From<ClearMsgMessage> for ClearMessage {
 	fn from(clear_msg: ClearMsgMessage) -> Self {
 		Self {
 			id: clear_msg.message_id,
+			channel: clear_msg.channel_login,
+			timestamp: clear_msg.server_timestamp.timestamp(),
 			author: clear_msg.sender_login,
 			message: clear_msg.message_text,
 		}
 	}
 }
+
+/// Ban or timeout of a user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ban {
+	/// Login name of the channel the user was banned in.
+	pub channel: String,
+	/// Timestamp when the user was banned.
+	pub timestamp: i64,
+	/// ID of the banned user.
+	pub user_id: String,
+	/// Login name of the banned user.
+	pub user_login: String,
+	/// Duration of the timeout, `None` for permanent bans.
+	pub duration: Option<Duration>,
+}
+
+impl Ban {
+	/// Get the ban from a ClearChat message, if it targets a user and not the
+	/// whole chat.
+	fn from_clear_chat(clear_chat: ClearChatMessage) -> Option<Self> {
+		let (user_id, user_login, duration) = match clear_chat.action {
+			ClearChatAction::ChatCleared => return None,
+			ClearChatAction::UserBanned { user_login, user_id } => (user_id, user_login, None),
+			ClearChatAction::UserTimedOut { user_login, user_id, timeout_length } => {
+				(user_id, user_login, Some(timeout_length))
+			}
+		};
+
+		Some(Self {
+			channel: clear_chat.channel_login,
+			timestamp: clear_chat.server_timestamp.timestamp(),
+			user_id,
+			user_login,
+			duration,
+		})
+	}
+}
+
+/// A user notice in the chat, like subscriptions, gifts or raids.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserNotice {
+	/// Unique ID of the notice.
+	pub id: String,
+	/// Login name of the channel the notice was sent in.
+	pub channel: String,
+	/// Timestamp when the notice was sent.
+	pub timestamp: i64,
+	/// User who caused the notice.
+	pub user: TwitchUserBasics,
+	/// Message text the user attached (if any).
+	pub message: Option<String>,
+	/// Message text generated by twitch, describing the event.
+	pub system_message: String,
+	/// The parsed event.
+	pub event: UserNoticeEvent,
+}
+
+/// Event of a user notice.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UserNoticeEvent {
+	/// New subscription.
+	Sub {
+		/// Subscription plan, e.g. `1000`, `2000`, `3000` or `Prime`.
+		plan: String,
+	},
+	/// Renewed subscription.
+	Resub {
+		/// Cumulative number of months subscribed.
+		months: u64,
+		/// Subscription plan, e.g. `1000`, `2000`, `3000` or `Prime`.
+		plan: String,
+	},
+	/// Subscription gifted to a single recipient.
+	SubGift {
+		/// Display name of the recipient.
+		recipient: String,
+		/// Subscription plan, e.g. `1000`, `2000` or `3000`.
+		plan: String,
+	},
+	/// Announcement of multiple gifted subscriptions. The single gifts follow
+	/// as separate [`UserNoticeEvent::SubGift`]s.
+	MassGift {
+		/// Number of subscriptions gifted.
+		count: u64,
+		/// Subscription plan, e.g. `1000`, `2000` or `3000`.
+		plan: String,
+	},
+	/// Incoming raid.
+	Raid {
+		/// Number of viewers raiding.
+		viewers: u64,
+	},
+	/// Announcement by a moderator.
+	Announcement,
+	/// Any other event, identified by twitch's event ID.
+	Other(String),
+}
+
+impl From<UserNoticeMessage> for UserNotice {
+	fn from(notice: UserNoticeMessage) -> Self {
+		let event = match notice.event {
+			TwitchUserNoticeEvent::SubOrResub { is_resub, cumulative_months, sub_plan, .. } => {
+				if is_resub {
+					UserNoticeEvent::Resub { months: cumulative_months, plan: sub_plan }
+				} else {
+					UserNoticeEvent::Sub { plan: sub_plan }
+				}
+			}
+			TwitchUserNoticeEvent::SubGift { recipient, sub_plan, .. } => {
+				UserNoticeEvent::SubGift { recipient: recipient.name, plan: sub_plan }
+			}
+			TwitchUserNoticeEvent::SubMysteryGift { mass_gift_count, sub_plan, .. }
+			| TwitchUserNoticeEvent::AnonSubMysteryGift { mass_gift_count, sub_plan, .. } => {
+				UserNoticeEvent::MassGift { count: mass_gift_count, plan: sub_plan }
+			}
+			TwitchUserNoticeEvent::Raid { viewer_count, .. } => {
+				UserNoticeEvent::Raid { viewers: viewer_count }
+			}
+			_ if notice.event_id == "announcement" => UserNoticeEvent::Announcement,
+			_ => UserNoticeEvent::Other(notice.event_id),
+		};
+
+		Self {
+			id: notice.message_id,
+			channel: notice.channel_login,
+			timestamp: notice.server_timestamp.timestamp(),
+			user: notice.sender,
+			message: notice.message_text,
+			system_message: notice.system_message,
+			event,
+		}
+	}
+}
+
+/// Tests of the chat receiver.
+#[cfg(test)]
+mod tests {
+	use tokio::sync::{mpsc, oneshot};
+
+	use super::{ChatEvent, ChatReceiver, ConnectionStatus, Message};
+	use crate::{
+		analytics::Analytics,
+		source::{MemorySource, SourceEvent},
+	};
+
+	/// Run a receiver on the events of a memory source until it is exhausted,
+	/// returning what reached the sink.
+	fn receive(source: MemorySource) -> Vec<SourceEvent> {
+		let (sender, mut received) = mpsc::unbounded_channel();
+		let (_stop_trigger, stop_trigger_receiver) = oneshot::channel();
+		ChatReceiver::builder()
+			.source(Box::new(source))
+			.sink(Box::new(sender))
+			.history(None)
+			.stop_trigger(stop_trigger_receiver)
+			.build()
+			.spawn()
+			.join()
+			.expect("running the receiver");
+
+		let mut events = Vec::new();
+		while let Ok(event) = received.try_recv() {
+			events.push(event);
+		}
+		events
+	}
+
+	/// Events reach the sink in order, with the messages flagged as spam.
+	#[test]
+	fn receiver_forwards_events_and_flags_spam() {
+		let source = [
+			SourceEvent::Status(ConnectionStatus::Joined),
+			SourceEvent::Chat(ChatEvent::Message(Message::test("a", "Alice", "hi there", 0))),
+			SourceEvent::Chat(ChatEvent::Message(Message::test("a", "Bob", "same again", 1))),
+			SourceEvent::Chat(ChatEvent::Message(Message::test("a", "Bob", "same again", 2))),
+			SourceEvent::Chat(ChatEvent::Message(Message::test("a", "Bob", "same again", 3))),
+		]
+		.into_iter()
+		.collect();
+
+		let events = receive(source);
+		assert_eq!(events.len(), 5);
+		assert!(matches!(events[0], SourceEvent::Status(ConnectionStatus::Joined)));
+		let spam = events
+			.iter()
+			.filter_map(|event| match event {
+				SourceEvent::Chat(ChatEvent::Message(message)) => Some(message.spam.is_some()),
+				_ => None,
+			})
+			.collect::<Vec<_>>();
+		assert_eq!(spam, [false, false, false, true]);
+	}
+
+	/// Received messages are counted per channel and enter the giveaway.
+	#[test]
+	fn analytics_count_received_messages_and_giveaway_entries() {
+		let source = [
+			Message::test("a", "Alice", "hello", 100),
+			Message::test("a", "Alice", "!enter", 110),
+			Message::test("b", "Bob", "!enter please", 120),
+			Message::test("b", "Carol", "enter", 130),
+			Message::test("a", "Alice", "!enter", 140),
+		]
+		.into_iter()
+		.map(ChatEvent::Message)
+		.collect::<MemorySource>();
+
+		let mut analytics = Analytics::default();
+		analytics.giveaway.message_start = "!enter".to_owned();
+		analytics.giveaway.open(100);
+		for event in receive(source) {
+			if let SourceEvent::Chat(event) = event {
+				analytics.handle_event(&event);
+			}
+		}
+
+		let metrics = &analytics.metrics;
+		assert_eq!(metrics.all.total_messages, 5);
+		assert_eq!(metrics.all.chatters.len(), 3);
+		assert_eq!(metrics.channel("a").total_messages, 3);
+		assert_eq!(metrics.channel("b").chatters.len(), 2);
+		assert_eq!(metrics.all.chatters.get("id-Alice").map(|chatter| chatter.messages), Some(3));
+
+		let entered = analytics
+			.giveaway
+			.people_entered
+			.iter()
+			.map(|entrant| entrant.name.as_str())
+			.collect::<Vec<_>>();
+		assert_eq!(entered, ["Alice", "Bob"]);
+	}
+}
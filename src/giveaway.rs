@@ -0,0 +1,798 @@
+//! Giveaway state and entry logic, independent of the UI.
+
+use std::{
+	collections::VecDeque,
+	fs::File,
+	path::{Path, PathBuf},
+};
+
+use color_eyre::Result;
+use im::{HashSet, Vector};
+use rand::seq::SliceRandom;
+use regex::Regex;
+
+use crate::{
+	chat::{Ban, Message},
+	metrics::ChatterStats,
+	settings::Settings,
+};
+
+/// The filename where the winners of the past giveaways are stored.
+const WINNERS_FILE: &str = "winners.json";
+
+/// How messages are matched to enter the giveaway.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "gui", derive(druid::Data))]
+pub enum MatchMode {
+	/// The first word of the message is the command.
+	#[default]
+	Command,
+	/// The message starts with the text.
+	Prefix,
+	/// The message starts with the text, ignoring case.
+	CaseInsensitive,
+	/// The message matches the regular expression.
+	Regex,
+	/// Any message.
+	Any,
+}
+
+/// Weights of the tickets of the people entered.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "gui", derive(druid::Data, druid::Lens))]
+pub struct TicketWeights {
+	/// Multiplier of the tickets of subscribers.
+	pub sub_multiplier: f64,
+	/// Additional multiplier of the tickets of subscribers per month of
+	/// tenure.
+	pub tenure_multiplier: f64,
+	/// Bits cheered in entering messages per additional ticket, 0 for none.
+	pub bits_per_ticket: u64,
+	/// Additional tickets of VIPs.
+	pub vip_bonus: f64,
+}
+
+impl Default for TicketWeights {
+	fn default() -> Self {
+		Self { sub_multiplier: 1.0, tenure_multiplier: 0.0, bits_per_ticket: 0, vip_bonus: 0.0 }
+	}
+}
+
+impl TicketWeights {
+	/// Number of tickets of the person entered. Everybody has one ticket, VIPs
+	/// and cheered bits add tickets and subscribers' tickets are multiplied.
+	#[must_use]
+	pub fn tickets(&self, entrant: &Entrant) -> f64 {
+		let mut tickets = 1.0;
+		if entrant.vip {
+			tickets += self.vip_bonus;
+		}
+		if self.bits_per_ticket > 0 {
+			tickets += (entrant.bits / self.bits_per_ticket) as f64;
+		}
+		if entrant.subscriber {
+			tickets *= self.sub_multiplier + self.tenure_multiplier * entrant.sub_months as f64;
+		}
+		tickets.max(0.0)
+	}
+}
+
+/// A person entered in the giveaway.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "gui", derive(druid::Data))]
+pub struct Entrant {
+	/// ID of the user.
+	pub id: String,
+	/// Display name.
+	pub name: String,
+	/// Whether they are a subscriber.
+	pub subscriber: bool,
+	/// Number of months they are subscribed.
+	pub sub_months: u64,
+	/// Whether they are a VIP.
+	pub vip: bool,
+	/// Number of bits cheered in their entering messages.
+	pub bits: u64,
+}
+
+impl Entrant {
+	/// New person entered with the message.
+	fn new(message: &Message) -> Self {
+		Self {
+			id: message.author.id.clone(),
+			name: message.author.name.clone(),
+			subscriber: message.subscriber,
+			sub_months: message.sub_months.unwrap_or_default(),
+			vip: message.is_vip(),
+			bits: message.bits.unwrap_or_default(),
+		}
+	}
+
+	/// Update with another entering message.
+	fn add_entry(&mut self, message: &Message) {
+		let bits = self.bits;
+		*self = Self::new(message);
+		self.bits += bits;
+	}
+}
+
+/// Rules who is eligible to enter the giveaway.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "gui", derive(druid::Data, druid::Lens))]
+pub struct EligibilityRules {
+	/// Whether only subscribers can enter.
+	pub subscribers_only: bool,
+	/// Minimum number of messages in the session before entering.
+	pub min_messages: usize,
+	/// Minimum number of minutes since the user first chatted in the channel,
+	/// according to the chat history, or in the session without it.
+	pub min_minutes: i64,
+	/// Whether moderators and the broadcaster are excluded.
+	pub exclude_moderators: bool,
+	/// Login names of excluded users, e.g. bots, separated by commas or
+	/// whitespace.
+	pub excluded_users: String,
+	/// Number of the last giveaways whose winners are excluded.
+	pub past_giveaways: usize,
+}
+
+impl EligibilityRules {
+	/// Whether the user with the login name is on the excluded users list.
+	fn excludes(&self, login: &str) -> bool {
+		self.excluded_users
+			.split(|c: char| c == ',' || c.is_whitespace())
+			.any(|user| user.eq_ignore_ascii_case(login))
+	}
+}
+
+/// An attempt to enter the giveaway that was not accepted, by an ineligible
+/// user or after it closed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "gui", derive(druid::Data))]
+pub struct Rejection {
+	/// ID of the user.
+	pub id: String,
+	/// Display name of the user.
+	pub name: String,
+	/// Timestamp of the attempt.
+	pub timestamp: i64,
+	/// Why the entry was not accepted.
+	pub reason: String,
+}
+
+/// State of the giveaway.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "gui", derive(druid::Data, druid::Lens))]
+pub struct Giveaway {
+	/// Selected channel to accept entries from, empty for all channels.
+	pub channel: String,
+	/// Text to enter the giveaway with, matched according to the mode.
+	pub message_start: String,
+	/// How messages are matched to enter the giveaway.
+	pub match_mode: MatchMode,
+	/// Whether the giveaway is open for entries.
+	pub open: bool,
+	/// Seconds the giveaway is open for entries, 0 for no time limit.
+	pub duration: i64,
+	/// Whether to draw the winners automatically when the time is up.
+	pub auto_draw: bool,
+	/// Seconds left until the giveaway closes, if open with a time limit.
+	pub remaining: Option<i64>,
+	/// Timestamp when the giveaway closes, if open with a time limit.
+	#[cfg_attr(feature = "gui", data(ignore))]
+	closes_at: Option<i64>,
+	/// Timestamp when the giveaway was closed, if it was opened since it was
+	/// cleared.
+	#[cfg_attr(feature = "gui", data(ignore))]
+	closed_at: Option<i64>,
+	/// Attempts to enter after the giveaway closed, newest first.
+	pub late_entries: Vector<Rejection>,
+	/// Compiled regular expression of the text, if in regex mode.
+	#[cfg_attr(feature = "gui", data(ignore))]
+	regex: Option<Regex>,
+	/// Whether to ignore the entries of users flagged as spamming.
+	pub ignore_spammers: bool,
+	/// IDs of the users flagged as spamming.
+	#[cfg_attr(feature = "gui", data(ignore))]
+	spammers: HashSet<String>,
+	/// People who entered the giveaway, in the order they entered.
+	pub people_entered: Vector<Entrant>,
+	/// Weights of the tickets of the people entered.
+	pub weights: TicketWeights,
+	/// Rules who is eligible to enter.
+	pub rules: EligibilityRules,
+	/// The latest attempt of every ineligible user to enter, newest first.
+	pub rejected: Vector<Rejection>,
+	/// Number of winners to draw.
+	pub winner_count: usize,
+	/// The drawn winners.
+	pub winners: Vector<Winner>,
+	/// IDs of the users who were rerolled and are excluded from further
+	/// draws.
+	#[cfg_attr(feature = "gui", data(ignore))]
+	excluded: HashSet<String>,
+	/// IDs of the winners of the past giveaways, newest first, including the
+	/// current one once winners were drawn.
+	#[cfg_attr(feature = "gui", data(ignore))]
+	past_winners: VecDeque<HashSet<String>>,
+	/// Whether the first past winners are the current giveaway's.
+	#[cfg_attr(feature = "gui", data(ignore))]
+	winners_recorded: bool,
+	/// File the winners of the past giveaways are stored in, if they are
+	/// stored at all.
+	#[cfg_attr(feature = "gui", data(ignore))]
+	file: Option<PathBuf>,
+	/// Number of each winner's messages kept, as configured in the settings.
+	#[cfg_attr(feature = "gui", data(ignore))]
+	buffer: usize,
+}
+
+impl Default for Giveaway {
+	fn default() -> Self {
+		Self {
+			channel: String::new(),
+			message_start: String::new(),
+			match_mode: MatchMode::default(),
+			open: false,
+			duration: 0,
+			auto_draw: false,
+			remaining: None,
+			closes_at: None,
+			closed_at: None,
+			late_entries: Vector::new(),
+			regex: None,
+			ignore_spammers: false,
+			spammers: HashSet::new(),
+			people_entered: Vector::new(),
+			weights: TicketWeights::default(),
+			rules: EligibilityRules::default(),
+			rejected: Vector::new(),
+			winner_count: 1,
+			winners: Vector::new(),
+			excluded: HashSet::new(),
+			past_winners: VecDeque::new(),
+			winners_recorded: false,
+			file: Some(WINNERS_FILE.into()),
+			buffer: 0,
+		}
+	}
+}
+
+/// A winner of the giveaway and their messages.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "gui", derive(druid::Data, druid::Lens))]
+pub struct Winner {
+	/// ID of the winner.
+	pub id: String,
+	/// Display name of the winner.
+	pub name: String,
+	/// The winner's messages since the draw, newest first.
+	#[cfg_attr(feature = "gui", data(same_fn = "fast_winners_messages_same"))]
+	pub messages: Vector<Message>,
+	/// Number of the messages kept.
+	#[cfg_attr(feature = "gui", data(ignore))]
+	buffer: usize,
+	/// Number of older messages paged in from the chat history, which are kept
+	/// in addition to the buffer.
+	#[cfg_attr(feature = "gui", data(ignore))]
+	scrollback: usize,
+}
+
+impl Winner {
+	/// New winner without messages, keeping the given number of messages.
+	fn new(id: String, name: String, buffer: usize) -> Self {
+		Self { id, name, messages: Vector::new(), buffer, scrollback: 0 }
+	}
+
+	/// Add a new message of the winner.
+	fn add_message(&mut self, message: &Message) {
+		self.messages.push_front(message.clone());
+		let buffer = self.buffer + self.scrollback;
+		if self.messages.len() > buffer {
+			self.messages.truncate(buffer);
+		}
+	}
+
+	/// Add older messages of the winner, paged in from the chat history.
+	pub fn add_older_messages(&mut self, messages: Vec<Message>) {
+		self.scrollback += messages.len();
+		self.messages.extend(messages);
+	}
+
+	/// Drop the older messages that were paged in.
+	pub fn reset_scrollback(&mut self) {
+		self.scrollback = 0;
+		self.messages.truncate(self.buffer);
+	}
+}
+
+impl Giveaway {
+	/// Maximum number of past giveaways whose winners are remembered.
+	const MAX_PAST_GIVEAWAYS: usize = 100;
+	/// Maximum number of late entries kept.
+	const MAX_LATE_ENTRIES: usize = 100;
+
+	/// Giveaway whose past winners are kept in memory only, without loading or
+	/// storing them.
+	#[must_use]
+	pub fn in_memory() -> Self {
+		Self { file: None, ..Self::default() }
+	}
+
+	/// Load the winners of the past giveaways from the file.
+	pub fn load_past_winners(&mut self) -> Result<()> {
+		if let Some(file) = &self.file {
+			self.past_winners = load_winners(file)?;
+			self.winners_recorded = false;
+		}
+		Ok(())
+	}
+
+	/// Apply new settings, which clears the giveaway.
+	pub fn apply_settings(&mut self, settings: &Settings) {
+		self.open = false;
+		self.closes_at = None;
+		self.remaining = None;
+		self.clear();
+		self.buffer = settings.chat_buffer;
+	}
+
+	/// The problem with the text to enter the giveaway with in the match mode,
+	/// if any.
+	#[must_use]
+	pub fn validation_error(&self) -> Option<String> {
+		let text = self.message_start.trim();
+		match self.match_mode {
+			MatchMode::Any => None,
+			_ if text.is_empty() => Some("Enter the text to enter the giveaway with".to_owned()),
+			MatchMode::Command if text.split_whitespace().nth(1).is_some() => {
+				Some("The command has to be a single word".to_owned())
+			}
+			MatchMode::Regex => {
+				Regex::new(&self.message_start).err().map(|err| format!("Invalid regex: {err}"))
+			}
+			MatchMode::Command | MatchMode::Prefix | MatchMode::CaseInsensitive => None,
+		}
+	}
+
+	/// Open the giveaway for entries at the timestamp, if the text to enter it
+	/// with is valid. It closes after the duration, if set.
+	pub fn open(&mut self, now: i64) {
+		if self.validation_error().is_some() {
+			return;
+		}
+		self.open = true;
+		self.closed_at = None;
+		self.closes_at = (self.duration > 0).then(|| now + self.duration);
+		self.remaining = self.closes_at.map(|closes_at| closes_at - now);
+	}
+
+	/// Close the giveaway for entries at the timestamp.
+	pub fn close(&mut self, now: i64) {
+		self.open = false;
+		self.closed_at = Some(now);
+		self.closes_at = None;
+		self.remaining = None;
+	}
+
+	/// Update the countdown at the timestamp. When the time is up, the
+	/// giveaway is closed and the winners are drawn, if enabled. To be called
+	/// periodically.
+	pub fn tick(&mut self, now: i64) {
+		if let (true, Some(closes_at)) = (self.open, self.closes_at) {
+			if now >= closes_at {
+				self.close(closes_at);
+				if self.auto_draw {
+					self.draw_winners();
+				}
+			} else {
+				self.remaining = Some(closes_at - now);
+			}
+		}
+	}
+
+	/// Whether the message text matches to enter the giveaway.
+	fn matches(&mut self, text: &str) -> bool {
+		let start = self.message_start.trim();
+		if start.is_empty() {
+			return self.match_mode == MatchMode::Any;
+		}
+
+		match self.match_mode {
+			MatchMode::Command => text.split_whitespace().next() == Some(start),
+			MatchMode::Prefix => text.starts_with(&self.message_start),
+			MatchMode::CaseInsensitive => {
+				text.to_lowercase().starts_with(&self.message_start.to_lowercase())
+			}
+			MatchMode::Regex => {
+				// The text may be edited any time, so recompile the regex when it
+				// changed.
+				if self.regex.as_ref().map_or(true, |regex| regex.as_str() != self.message_start) {
+					self.regex = Regex::new(&self.message_start).ok();
+				}
+				self.regex.as_ref().map_or(false, |regex| regex.is_match(text))
+			}
+			MatchMode::Any => true,
+		}
+	}
+
+	/// Whether the giveaway accepts entries from the channel.
+	fn accepts(&self, channel: &str) -> bool {
+		self.channel.is_empty() || self.channel == channel
+	}
+
+	/// Whether the user's entries are ignored for spamming.
+	fn is_ignored(&self, user_id: &str) -> bool {
+		self.ignore_spammers && self.spammers.contains(user_id)
+	}
+
+	/// Why the author of the message is not eligible to enter, if they are not.
+	/// The chatter's statistics in the session include the message.
+	fn ineligibility(&self, message: &Message, chatter: Option<&ChatterStats>) -> Option<String> {
+		let rules = &self.rules;
+		let (messages, first_seen) = chatter
+			.map_or((1, message.timestamp), |chatter| (chatter.messages, chatter.first_seen));
+		let prior_messages = messages.saturating_sub(1);
+		let first_chatted = message.first_chatted.map_or(first_seen, |first| first.min(first_seen));
+		let minutes = (message.timestamp - first_chatted) / 60;
+
+		if self.is_ignored(&message.author.id) {
+			Some("Flagged as spamming".to_owned())
+		} else if rules.excludes(&message.author.login) {
+			Some("On the excluded users list".to_owned())
+		} else if rules.exclude_moderators && message.is_moderator() {
+			Some("Moderators are excluded".to_owned())
+		} else if rules.subscribers_only && !message.subscriber {
+			Some("Subscribers only".to_owned())
+		} else if prior_messages < rules.min_messages {
+			Some(format!(
+				"Sent {prior_messages} of {} required messages before entering",
+				rules.min_messages
+			))
+		} else if minutes < rules.min_minutes {
+			Some(format!(
+				"First chatted {minutes} minutes before entering, {} required",
+				rules.min_minutes
+			))
+		} else if self
+			.past_winners
+			.iter()
+			.take(rules.past_giveaways)
+			.any(|winners| winners.contains(&message.author.id))
+		{
+			Some(format!("Won one of the last {} giveaways", rules.past_giveaways))
+		} else {
+			None
+		}
+	}
+
+	/// Handle a new chat message, entering its author if it matches and they
+	/// are eligible. The author's statistics in the session, if available, are
+	/// needed for the eligibility rules.
+	pub fn handle_message(&mut self, message: &Message, chatter: Option<&ChatterStats>) {
+		// The time might be up before the countdown ticks again.
+		if self.open && self.closes_at.map_or(false, |closes_at| message.timestamp >= closes_at) {
+			self.tick(message.timestamp);
+		}
+		if !self.accepts(&message.channel) {
+			return;
+		}
+
+		let id = &message.author.id;
+		if message.spam.is_some() {
+			self.spammers.insert(id.clone());
+		}
+		if self.is_ignored(id) {
+			self.remove_entrant(id);
+		}
+		if self.open && self.matches(&message.message) {
+			match self.ineligibility(message, chatter) {
+				Some(reason) => {
+					self.remove_entrant(id);
+					self.remove_rejection(id);
+					self.rejected.push_front(Rejection {
+						id: id.clone(),
+						name: message.author.name.clone(),
+						timestamp: message.timestamp,
+						reason,
+					});
+				}
+				None => {
+					self.remove_rejection(id);
+					match self.people_entered.iter_mut().find(|entrant| entrant.id == *id) {
+						Some(entrant) => entrant.add_entry(message),
+						None => self.people_entered.push_back(Entrant::new(message)),
+					}
+				}
+			}
+		} else if let Some(closed_at) = self.closed_at {
+			if self.matches(&message.message) {
+				self.late_entries.push_front(Rejection {
+					id: id.clone(),
+					name: message.author.name.clone(),
+					timestamp: message.timestamp,
+					reason: format!("{}s after closing", (message.timestamp - closed_at).max(0)),
+				});
+				self.late_entries.truncate(Self::MAX_LATE_ENTRIES);
+			}
+		}
+		for winner in self.winners.iter_mut().filter(|winner| winner.id == message.author.id) {
+			winner.add_message(message);
+		}
+	}
+
+	/// Handle a ban or timeout. Banned users are not allowed to take part in
+	/// the giveaway.
+	pub fn handle_ban(&mut self, ban: &Ban) {
+		if ban.duration.is_none() && self.accepts(&ban.channel) {
+			self.remove_entrant(&ban.user_id);
+		}
+	}
+
+	/// Remove the user with the ID from the people entered, if they entered.
+	fn remove_entrant(&mut self, id: &str) {
+		if self.people_entered.iter().any(|entrant| entrant.id == id) {
+			self.people_entered.retain(|entrant| entrant.id != id);
+		}
+	}
+
+	/// Remove the rejected entry of the user with the ID, if there is one.
+	fn remove_rejection(&mut self, id: &str) {
+		if self.rejected.iter().any(|rejection| rejection.id == id) {
+			self.rejected.retain(|rejection| rejection.id != id);
+		}
+	}
+
+	/// Clear the entries and the winners for the next giveaway. The winners
+	/// were already remembered as winners of the past giveaway when drawn.
+	pub fn clear(&mut self) {
+		self.winners_recorded = false;
+		self.people_entered.clear();
+		self.rejected.clear();
+		self.late_entries.clear();
+		self.closed_at = None;
+		self.winners.clear();
+		self.excluded.clear();
+	}
+
+	/// The people entered who can win: no ignored spammers, no rerolled users
+	/// and no current winners.
+	fn candidates(&self) -> Vec<&Entrant> {
+		self.people_entered
+			.iter()
+			.filter(|entrant| {
+				!self.is_ignored(&entrant.id)
+					&& !self.excluded.contains(&entrant.id)
+					&& !self.winners.iter().any(|winner| winner.id == entrant.id)
+			})
+			.collect()
+	}
+
+	/// Draw the configured number of distinct random winners from the people
+	/// entered, weighted by their tickets, replacing the previous winners.
+	pub fn draw_winners(&mut self) {
+		self.winners.clear();
+		let mut rng = rand::thread_rng();
+		let winners: Vector<Winner> = self
+			.candidates()
+			.choose_multiple_weighted(&mut rng, self.winner_count.max(1), |entrant| {
+				self.weights.tickets(entrant)
+			})
+			.map(|winners| {
+				winners
+					.map(|entrant| {
+						Winner::new(entrant.id.clone(), entrant.name.clone(), self.buffer)
+					})
+					.collect()
+			})
+			.unwrap_or_default();
+		self.winners = winners;
+		self.record_winners();
+	}
+
+	/// Remember the current winners as the winners of the current giveaway,
+	/// replacing those of its earlier draws, and store the past winners.
+	fn record_winners(&mut self) {
+		if self.winners.is_empty() && !self.winners_recorded {
+			return;
+		}
+		if !self.winners_recorded {
+			self.past_winners.push_front(HashSet::new());
+			self.past_winners.truncate(Self::MAX_PAST_GIVEAWAYS);
+			self.winners_recorded = true;
+		}
+		if let Some(recorded) = self.past_winners.front_mut() {
+			*recorded = self.winners.iter().map(|winner| winner.id.clone()).collect();
+		}
+		if let Some(file) = &self.file {
+			if let Err(err) = save_winners(file, &self.past_winners) {
+				tracing::error!("Saving giveaway winners failed: {err}");
+			}
+		}
+	}
+
+	/// Reroll the winner with the ID, e.g. because they did not respond. They
+	/// are excluded from further draws and replaced by a new random winner,
+	/// weighted by the tickets, if there are people left.
+	pub fn reroll(&mut self, winner_id: &str) {
+		let index = match self.winners.iter().position(|winner| winner.id == winner_id) {
+			Some(index) => index,
+			None => return,
+		};
+		self.excluded.insert(winner_id.to_owned());
+
+		let mut rng = rand::thread_rng();
+		let replacement = self
+			.candidates()
+			.choose_weighted(&mut rng, |entrant| self.weights.tickets(entrant))
+			.ok()
+			.map(|entrant| Winner::new(entrant.id.clone(), entrant.name.clone(), self.buffer));
+		match replacement {
+			Some(winner) => self.winners[index] = winner,
+			None => {
+				self.winners.remove(index);
+			}
+		}
+		// Rerolled users did not actually win.
+		self.record_winners();
+	}
+}
+
+/// Load the winners of the past giveaways from the file, newest first.
+fn load_winners(path: &Path) -> Result<VecDeque<HashSet<String>>> {
+	let file = File::open(path)?;
+	let past: Vec<Vec<String>> = serde_json::from_reader(file)?;
+	Ok(past.into_iter().map(|winners| winners.into_iter().collect()).collect())
+}
+
+/// Save the winners of the past giveaways to the file, newest first.
+fn save_winners(path: &Path, past: &VecDeque<HashSet<String>>) -> Result<()> {
+	let file = File::create(path)?;
+	let past: Vec<Vec<&String>> = past.iter().map(|winners| winners.iter().collect()).collect();
+	serde_json::to_writer_pretty(file, &past)?;
+	Ok(())
+}
+
+/// Fast way to make sure two vectors of messages are not the same: check first
+/// and last element for equality.
+#[cfg(feature = "gui")]
+fn fast_winners_messages_same(a: &Vector<Message>, b: &Vector<Message>) -> bool {
+	a.front() == b.front() && a.back() == b.back()
+}
+
+/// Tests of the giveaway.
+#[cfg(test)]
+mod tests {
+	use super::Giveaway;
+	use crate::chat::{Ban, Message};
+
+	/// Giveaway entered with a command, excluding the winners of the last one.
+	fn giveaway() -> Giveaway {
+		let mut giveaway = Giveaway { message_start: "!enter".to_owned(), ..Giveaway::in_memory() };
+		giveaway.rules.past_giveaways = 1;
+		giveaway
+	}
+
+	/// Enter the user in the giveaway at the timestamp.
+	fn enter(giveaway: &mut Giveaway, user: &str, timestamp: i64) {
+		giveaway.handle_message(&Message::test("a", user, "!enter", timestamp), None);
+	}
+
+	/// IDs of the people entered, in the order they entered.
+	fn entered(giveaway: &Giveaway) -> Vec<&str> {
+		giveaway.people_entered.iter().map(|entrant| entrant.id.as_str()).collect()
+	}
+
+	/// IDs of the users whose entries were rejected, newest first.
+	fn rejected(giveaway: &Giveaway) -> Vec<&str> {
+		giveaway.rejected.iter().map(|rejection| rejection.id.as_str()).collect()
+	}
+
+	/// Winners are remembered when drawn, and a new draw of the same giveaway
+	/// replaces the winners of the earlier draw.
+	#[test]
+	fn winners_are_remembered_when_drawn() {
+		let mut giveaway = giveaway();
+		giveaway.open(0);
+		enter(&mut giveaway, "Alice", 1);
+		giveaway.close(2);
+		giveaway.draw_winners();
+		assert_eq!(giveaway.winners[0].name, "Alice");
+
+		giveaway.open(3);
+		enter(&mut giveaway, "Bob", 4);
+		giveaway.handle_ban(&Ban {
+			channel: "a".to_owned(),
+			timestamp: 5,
+			user_id: "id-Alice".to_owned(),
+			user_login: "alice".to_owned(),
+			duration: None,
+		});
+		giveaway.close(6);
+		giveaway.draw_winners();
+		assert_eq!(giveaway.winners[0].name, "Bob");
+
+		giveaway.clear();
+		giveaway.open(7);
+		enter(&mut giveaway, "Alice", 8);
+		enter(&mut giveaway, "Bob", 9);
+		enter(&mut giveaway, "Carol", 10);
+		assert_eq!(rejected(&giveaway), ["id-Bob"]);
+		assert_eq!(entered(&giveaway), ["id-Alice", "id-Carol"]);
+	}
+
+	/// The winners of past giveaways are stored and excluded after loading
+	/// them again.
+	#[test]
+	fn past_winners_are_stored() {
+		let file = std::env::temp_dir()
+			.join(format!("twitch-chat-metrics-winners-{}.json", std::process::id()));
+		let mut stored = Giveaway { file: Some(file.clone()), ..giveaway() };
+		stored.open(0);
+		enter(&mut stored, "Alice", 1);
+		stored.close(2);
+		stored.draw_winners();
+
+		let mut loaded = Giveaway { file: Some(file.clone()), ..giveaway() };
+		let result = loaded.load_past_winners();
+		std::fs::remove_file(&file).ok();
+		result.expect("loading past winners");
+		loaded.open(3);
+		enter(&mut loaded, "Alice", 4);
+		assert_eq!(rejected(&loaded), ["id-Alice"]);
+	}
+
+	/// The minutes since first chatting count from the first message in the
+	/// chat history, if known.
+	#[test]
+	fn minutes_count_from_the_first_message_in_the_history() {
+		let mut giveaway = giveaway();
+		giveaway.rules.min_minutes = 5;
+		giveaway.open(0);
+		let mut message = Message::test("a", "Alice", "!enter", 600);
+		message.first_chatted = Some(0);
+		giveaway.handle_message(&message, None);
+		enter(&mut giveaway, "Bob", 610);
+		assert_eq!(entered(&giveaway), ["id-Alice"]);
+		assert_eq!(rejected(&giveaway), ["id-Bob"]);
+	}
+
+	/// Rerolled winners do not count as winners.
+	#[test]
+	fn rerolled_winners_are_not_remembered() {
+		let mut giveaway = giveaway();
+		giveaway.open(0);
+		enter(&mut giveaway, "Alice", 1);
+		enter(&mut giveaway, "Bob", 2);
+		giveaway.close(3);
+		giveaway.draw_winners();
+		let rerolled = giveaway.winners[0].id.clone();
+		giveaway.reroll(&rerolled);
+		let winner = giveaway.winners[0].id.clone();
+		assert_ne!(winner, rerolled);
+
+		giveaway.clear();
+		giveaway.open(4);
+		enter(&mut giveaway, "Alice", 5);
+		enter(&mut giveaway, "Bob", 6);
+		assert_eq!(entered(&giveaway), [rerolled.as_str()]);
+		assert_eq!(rejected(&giveaway), [winner.as_str()]);
+	}
+
+	/// Entries sent after the time is up are late, even before the countdown
+	/// ticks.
+	#[test]
+	fn entries_after_the_time_is_up_are_late() {
+		let mut giveaway = Giveaway { duration: 60, auto_draw: true, ..giveaway() };
+		giveaway.open(0);
+		enter(&mut giveaway, "Alice", 30);
+		enter(&mut giveaway, "Bob", 61);
+		assert!(!giveaway.open);
+		assert_eq!(entered(&giveaway), ["id-Alice"]);
+		assert_eq!(giveaway.late_entries.len(), 1);
+		assert_eq!(giveaway.late_entries[0].name, "Bob");
+		assert_eq!(giveaway.late_entries[0].reason, "1s after closing");
+		assert_eq!(giveaway.winners.len(), 1);
+		assert_eq!(giveaway.winners[0].name, "Alice");
+	}
+}
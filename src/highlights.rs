@@ -0,0 +1,213 @@
+//! Detection of chat activity spikes, recorded as highlights of the stream to
+//! find clip-worthy moments in the VOD.
+
+use std::{fs::File, io::Write, path::Path};
+
+use color_eyre::Result;
+use im::{HashMap, Vector};
+use serde::Serialize;
+
+use crate::{metrics::Metrics, session::format_time, settings::Settings};
+
+/// A spike of chat activity.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "gui", derive(druid::Data))]
+pub struct Highlight {
+	/// Channel the spike happened in.
+	pub channel: String,
+	/// Timestamp of the start of the spike.
+	pub started: i64,
+	/// Timestamp of the end of the spike.
+	pub ended: i64,
+	/// Seconds since the start of the session, if one was running.
+	pub offset: Option<i64>,
+	/// Highest number of messages in one minute of the spike.
+	pub peak_messages: usize,
+	/// Average number of messages per minute before the spike.
+	pub average: f64,
+	/// The dominant terms in the peak minute, separated by commas.
+	pub terms: String,
+	/// The dominant emotes in the peak minute, separated by commas.
+	pub emotes: String,
+}
+
+impl Highlight {
+	/// Extend the highlight by the following spike.
+	fn extend(&mut self, next: Self) {
+		self.ended = next.ended;
+		if next.peak_messages > self.peak_messages {
+			self.peak_messages = next.peak_messages;
+			self.terms = next.terms;
+			self.emotes = next.emotes;
+		}
+	}
+}
+
+/// Detector of minutes with more messages than the moving average by a
+/// factor.
+#[derive(Debug, Clone, Default)]
+pub struct SpikeDetector {
+	/// Factor the messages of a minute have to exceed the average by, zero to
+	/// disable the detection.
+	factor: f64,
+}
+
+impl SpikeDetector {
+	/// Number of minutes before the checked one that make up the moving
+	/// average.
+	const WINDOW: usize = 5;
+	/// Minimum number of messages in a minute to be a spike.
+	const MIN_MESSAGES: usize = 10;
+	/// Number of dominant terms and emotes recorded.
+	const TOP: usize = 3;
+
+	/// Create the detector configured in the settings.
+	#[must_use]
+	pub fn from_settings(settings: &Settings) -> Self {
+		Self { factor: settings.spike_factor }
+	}
+
+	/// Check the minute of the channel's metrics for a spike.
+	#[must_use]
+	pub fn detect(&self, channel: &str, metrics: &Metrics, minute: i64) -> Option<Highlight> {
+		if self.factor <= 0.0 {
+			return None;
+		}
+
+		let buckets = metrics.series.buckets();
+		let first = buckets.front()?.minute;
+		let index = usize::try_from(minute - first).ok()?;
+		let bucket = buckets.get(index)?;
+		if bucket.messages < Self::MIN_MESSAGES || index < Self::WINDOW {
+			return None;
+		}
+
+		let previous = buckets.iter().skip(index - Self::WINDOW).take(Self::WINDOW);
+		let average =
+			previous.map(|bucket| bucket.messages as f64).sum::<f64>() / Self::WINDOW as f64;
+		if (bucket.messages as f64) <= self.factor * average {
+			return None;
+		}
+
+		Some(Highlight {
+			channel: channel.to_owned(),
+			started: minute * 60,
+			ended: (minute + 1) * 60,
+			offset: None,
+			peak_messages: bucket.messages,
+			average,
+			terms: metrics.terms.minute_counts(minute).map(top_keys).unwrap_or_default(),
+			emotes: top_keys(bucket.emote_uses()),
+		})
+	}
+}
+
+/// Add the highlight to the list, extending the channel's last highlight if it
+/// directly precedes it.
+pub fn add(highlights: &mut Vector<Highlight>, highlight: Highlight) {
+	match highlights.iter_mut().rev().find(|last| last.channel == highlight.channel) {
+		Some(last) if last.ended == highlight.started => last.extend(highlight),
+		_ => highlights.push_back(highlight),
+	}
+}
+
+/// The most counted keys, separated by commas.
+fn top_keys(counts: &HashMap<String, usize>) -> String {
+	let mut counts = counts.iter().collect::<Vec<_>>();
+	counts.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+	counts
+		.into_iter()
+		.take(SpikeDetector::TOP)
+		.map(|(key, _)| key.as_str())
+		.collect::<Vec<_>>()
+		.join(", ")
+}
+
+/// Format seconds as hours, minutes and seconds, like VOD timestamps.
+#[must_use]
+pub fn format_offset(seconds: i64) -> String {
+	format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+/// Export the highlights to a CSV file.
+pub fn export_csv<'a>(
+	path: &Path,
+	highlights: impl IntoIterator<Item = &'a Highlight>,
+) -> Result<()> {
+	let mut file = File::create(path)?;
+	writeln!(file, "channel,started,ended,vod_offset,peak_messages,average_messages,terms,emotes")?;
+	for highlight in highlights {
+		writeln!(
+			file,
+			"{},{},{},{},{},{:.1},{},{}",
+			csv_field(&highlight.channel),
+			format_time(highlight.started),
+			format_time(highlight.ended),
+			highlight.offset.map(format_offset).unwrap_or_default(),
+			highlight.peak_messages,
+			highlight.average,
+			csv_field(&highlight.terms),
+			csv_field(&highlight.emotes),
+		)?;
+	}
+	Ok(())
+}
+
+/// Quote the CSV field.
+fn csv_field(value: &str) -> String {
+	format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+/// Tests of the spike detection.
+#[cfg(test)]
+mod tests {
+	use super::SpikeDetector;
+	use crate::{
+		chat::Message,
+		metrics::{ChatMetrics, Metrics},
+		settings::Settings,
+	};
+
+	/// Metrics of a channel with the number of messages per minute, starting at
+	/// minute 0.
+	fn metrics(messages_per_minute: &[i64]) -> Metrics {
+		let mut metrics = ChatMetrics::default();
+		for (minute, messages) in (0..).zip(messages_per_minute) {
+			for second in 0..*messages {
+				metrics.add_message(&Message::test("a", "Alice", "pog", minute * 60 + second));
+			}
+		}
+		metrics.channel("a")
+	}
+
+	/// A minute with more messages than the factor times the average before is
+	/// a spike.
+	#[test]
+	fn spike_is_detected() {
+		let detector = SpikeDetector::from_settings(&Settings::default());
+		let metrics = metrics(&[4, 6, 5, 5, 5, 12, 10]);
+
+		let highlight = detector.detect("a", &metrics, 5).expect("spike detected");
+		assert_eq!(highlight.channel, "a");
+		assert_eq!((highlight.started, highlight.ended), (300, 360));
+		assert_eq!(highlight.peak_messages, 12);
+		assert!((highlight.average - 5.0).abs() < f64::EPSILON);
+
+		// Not more than twice the average of 6.6 before.
+		assert!(detector.detect("a", &metrics, 6).is_none());
+	}
+
+	/// There are no spikes without enough minutes before or too few messages,
+	/// or if the detection is disabled.
+	#[test]
+	fn no_spike_without_enough_activity() {
+		let detector = SpikeDetector::from_settings(&Settings::default());
+		assert!(detector.detect("a", &metrics(&[1, 1, 1, 20]), 3).is_none());
+		assert!(detector.detect("a", &metrics(&[1, 1, 1, 1, 1, 9]), 5).is_none());
+		assert!(detector.detect("a", &metrics(&[1, 1, 1, 1, 1, 20]), 9).is_none());
+
+		let disabled =
+			SpikeDetector::from_settings(&Settings { spike_factor: 0.0, ..Settings::default() });
+		assert!(disabled.detect("a", &metrics(&[1, 1, 1, 1, 1, 20]), 5).is_none());
+	}
+}
@@ -0,0 +1,384 @@
+//! Persistent chat history, stored in one SQLite database per channel.
+
+use std::{
+	collections::{hash_map::Entry, HashMap, HashSet},
+	fs,
+	path::{Path, PathBuf},
+	sync::mpsc::{self, Receiver, Sender},
+	thread::{self, JoinHandle},
+	time::{Duration, Instant},
+};
+
+use color_eyre::{eyre::bail, Result};
+use rusqlite::{params, Connection, OpenFlags};
+
+use crate::{
+	chat::{ChatEvent, Message},
+	settings::Settings,
+};
+
+/// Directory the chat history databases are stored in.
+pub const DIRECTORY: &str = "history";
+/// Maximum number of events written in one transaction.
+const BATCH_SIZE: usize = 100;
+/// Maximum time events are held back before they are written.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The writer of the chat history and the lookup of the known chatters, if
+/// the history is used with the settings.
+#[must_use]
+pub fn from_settings(settings: &Settings) -> (Option<HistoryWriter>, Option<KnownChatters>) {
+	if settings.uses_history() {
+		(Some(HistoryWriter::spawn(DIRECTORY)), Some(KnownChatters::new(DIRECTORY)))
+	} else {
+		(None, None)
+	}
+}
+
+/// Path of the database of the given channel. Channel names come from the
+/// settings, recordings or the server, so only twitch login names are allowed
+/// to not write anywhere else.
+fn database_path(directory: &Path, channel: &str) -> Result<PathBuf> {
+	if channel.is_empty()
+		|| !channel.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+	{
+		bail!("Invalid channel name {channel:?}");
+	}
+	Ok(directory.join(format!("{channel}.sqlite")))
+}
+
+/// Open the database of the given channel, creating it if necessary.
+fn open_database(directory: &Path, channel: &str) -> Result<Connection> {
+	let connection = Connection::open(database_path(directory, channel)?)?;
+	connection.execute_batch(
+		"CREATE TABLE IF NOT EXISTS events (
+			id INTEGER PRIMARY KEY AUTOINCREMENT,
+			timestamp INTEGER NOT NULL,
+			kind TEXT NOT NULL,
+			user_id TEXT,
+			payload TEXT NOT NULL
+		);
+		CREATE INDEX IF NOT EXISTS events_kind_timestamp ON events (kind, timestamp);
+		CREATE INDEX IF NOT EXISTS events_user_id ON events (user_id);",
+	)?;
+	Ok(connection)
+}
+
+/// Writer of chat events to the history. The events are written in batches
+/// on a separate thread, which finishes once the writer is dropped.
+#[derive(Debug)]
+pub struct HistoryWriter {
+	/// Sender of events to the writer thread.
+	sender: Sender<ChatEvent>,
+	/// The writer thread's join handle.
+	thread: JoinHandle<()>,
+}
+
+impl HistoryWriter {
+	/// Spawn the writer thread, storing the databases in the given directory.
+	#[must_use]
+	pub fn spawn(directory: impl Into<PathBuf>) -> Self {
+		let directory = directory.into();
+		let (sender, receiver) = mpsc::channel();
+		let thread = thread::spawn(move || {
+			if let Err(err) = write_events(&directory, &receiver) {
+				tracing::error!("Writing chat history failed: {err}");
+			}
+		});
+		Self { sender, thread }
+	}
+
+	/// Queue the event to be written.
+	pub fn write(&self, event: ChatEvent) {
+		self.sender.send(event).ok();
+	}
+
+	/// Stop the writer, waiting until all queued events are written.
+	pub fn finish(self) {
+		drop(self.sender);
+		if self.thread.join().is_err() {
+			tracing::error!("Chat history writer thread panicked");
+		}
+	}
+}
+
+/// Chatters known from the history, i.e. who chatted in a channel before, and
+/// when they first chatted there. They are loaded once per channel when first
+/// asked for.
+#[derive(Debug)]
+pub struct KnownChatters {
+	/// Directory the databases are stored in.
+	directory: PathBuf,
+	/// Timestamps of the first messages of the known chatters by user ID, by
+	/// channel.
+	channels: HashMap<String, HashMap<String, i64>>,
+}
+
+impl KnownChatters {
+	/// Create the lookup of the chatters in the history in the given directory.
+	#[must_use]
+	pub fn new(directory: impl Into<PathBuf>) -> Self {
+		Self { directory: directory.into(), channels: HashMap::new() }
+	}
+
+	/// When the user first chatted in the channel, if they chatted there
+	/// before. Otherwise the timestamp of their message is remembered as their
+	/// first, so that they count as returning in later sessions of the same
+	/// run, too.
+	pub fn check_and_remember(
+		&mut self,
+		channel: &str,
+		user_id: &str,
+		timestamp: i64,
+	) -> Option<i64> {
+		let directory = &self.directory;
+		let chatters = self.channels.entry(channel.to_owned()).or_insert_with(|| {
+			load_chatters(directory, channel).unwrap_or_else(|err| {
+				tracing::error!("Loading known chatters of {channel} failed: {err}");
+				HashMap::new()
+			})
+		});
+		match chatters.entry(user_id.to_owned()) {
+			Entry::Occupied(first) => Some(*first.get()),
+			Entry::Vacant(entry) => {
+				entry.insert(timestamp);
+				None
+			}
+		}
+	}
+}
+
+/// Load the timestamps of the first messages of all chatters who sent a
+/// message in the channel, by user ID.
+fn load_chatters(directory: &Path, channel: &str) -> Result<HashMap<String, i64>> {
+	if !database_path(directory, channel)?.exists() {
+		return Ok(HashMap::new());
+	}
+
+	let connection = open_database(directory, channel)?;
+	let mut statement = connection.prepare(
+		"SELECT user_id, MIN(timestamp) FROM events WHERE kind = 'message' AND user_id IS NOT \
+		 NULL GROUP BY user_id",
+	)?;
+	let chatters =
+		statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?.collect::<Result<_, _>>()?;
+	Ok(chatters)
+}
+
+/// Query of the messages sent before the loaded ones. It holds no reference to
+/// the loaded messages, so it can be run on another thread.
+#[derive(Debug, Clone)]
+pub struct OlderMessages {
+	/// Channels whose histories are queried.
+	channels: Vec<String>,
+	/// ID of the user to query the messages of, or all users if not given.
+	user_id: Option<String>,
+	/// Timestamp of the oldest loaded message.
+	before: i64,
+	/// IDs of the loaded messages sent in the same second as the oldest one.
+	same_second: HashSet<String>,
+	/// Maximum number of messages queried.
+	count: usize,
+}
+
+impl OlderMessages {
+	/// Query up to `count` messages sent before the given loaded messages,
+	/// which are ordered newest first, in the channels, optionally only the
+	/// messages of one user.
+	#[must_use]
+	pub fn new<'a>(
+		channels: Vec<String>,
+		user_id: Option<String>,
+		loaded: impl IntoIterator<Item = &'a Message>,
+		count: usize,
+	) -> Self {
+		// Timestamps are in seconds, so messages of the same second as the oldest
+		// loaded message might still be missing.
+		let mut before = i64::MAX;
+		let mut same_second = HashSet::new();
+		for message in loaded {
+			if message.timestamp < before {
+				before = message.timestamp;
+				same_second.clear();
+			}
+			if message.timestamp == before {
+				same_second.insert(message.id.clone());
+			}
+		}
+
+		Self { channels, user_id, before, same_second, count }
+	}
+
+	/// Load the messages from the histories in the directory, newest first.
+	pub fn load(&self, directory: &Path) -> Result<Vec<Message>> {
+		let mut messages = Vec::new();
+		for channel in &self.channels {
+			let path = database_path(directory, channel)?;
+			if !path.exists() {
+				continue;
+			}
+
+			let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+			connection.busy_timeout(Duration::from_secs(1))?;
+			let mut statement = connection.prepare(
+				"SELECT payload FROM events WHERE kind = 'message' AND timestamp <= ?1 AND (?2 \
+				 IS NULL OR user_id = ?2) ORDER BY timestamp DESC, id DESC LIMIT ?3",
+			)?;
+			let limit = i64::try_from(self.count + self.same_second.len()).unwrap_or(i64::MAX);
+			let payloads = statement
+				.query_map(params![self.before, self.user_id, limit], |row| {
+					row.get::<_, String>(0)
+				})?
+				.collect::<Result<Vec<_>, _>>()?;
+			for payload in payloads {
+				if let ChatEvent::Message(message) = serde_json::from_str(&payload)? {
+					if !self.same_second.contains(&message.id) {
+						messages.push(message);
+					}
+				}
+			}
+		}
+
+		messages.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+		messages.truncate(self.count);
+		Ok(messages)
+	}
+}
+
+/// Receive events and write them in batches until all senders are dropped.
+/// Failed batches are logged and dropped, so that later events are still
+/// written, e.g. once a locked database or a full disk is available again.
+fn write_events(directory: &Path, receiver: &Receiver<ChatEvent>) -> Result<()> {
+	fs::create_dir_all(directory)?;
+	let mut databases = HashMap::new();
+
+	while let Ok(event) = receiver.recv() {
+		let deadline = Instant::now() + FLUSH_INTERVAL;
+		let mut batch = vec![event];
+		while batch.len() < BATCH_SIZE {
+			match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+				Ok(event) => batch.push(event),
+				Err(_) => break,
+			}
+		}
+
+		let count = batch.len();
+		if let Err(err) = write_batch(directory, &mut databases, batch) {
+			tracing::error!("Writing {count} chat events to the history failed: {err}");
+		}
+	}
+
+	Ok(())
+}
+
+/// Write a batch of events to the databases of their channels, one transaction
+/// per channel.
+fn write_batch(
+	directory: &Path,
+	databases: &mut HashMap<String, Connection>,
+	batch: Vec<ChatEvent>,
+) -> Result<()> {
+	let mut by_channel: HashMap<String, Vec<ChatEvent>> = HashMap::new();
+	for event in batch {
+		by_channel.entry(event.channel().to_owned()).or_default().push(event);
+	}
+
+	for (channel, events) in by_channel {
+		let connection = match databases.entry(channel) {
+			Entry::Occupied(entry) => entry.into_mut(),
+			Entry::Vacant(entry) => match open_database(directory, entry.key()) {
+				Ok(connection) => entry.insert(connection),
+				// Skip the channel only, e.g. if its name is invalid.
+				Err(err) => {
+					tracing::error!("Opening the history of {:?} failed: {err}", entry.key());
+					continue;
+				}
+			},
+		};
+
+		let transaction = connection.transaction()?;
+		for event in events {
+			transaction.execute(
+				"INSERT INTO events (timestamp, kind, user_id, payload) VALUES (?1, ?2, ?3, ?4)",
+				params![
+					event.timestamp(),
+					event.kind(),
+					event.user_id(),
+					serde_json::to_string(&event)?
+				],
+			)?;
+		}
+		transaction.commit()?;
+	}
+
+	Ok(())
+}
+
+/// Tests of the chat history.
+#[cfg(test)]
+mod tests {
+	use std::{fs, path::Path};
+
+	use super::{database_path, HistoryWriter, KnownChatters, OlderMessages};
+	use crate::chat::{ChatEvent, Message};
+
+	/// Only twitch login names are used as database file names.
+	#[test]
+	fn invalid_channel_names_are_rejected() {
+		let directory = Path::new("history");
+		assert_eq!(
+			database_path(directory, "some_channel42").ok(),
+			Some(directory.join("some_channel42.sqlite"))
+		);
+		for channel in ["", "../settings", "Channel", "a/b", "a.b", "#channel"] {
+			assert!(database_path(directory, channel).is_err(), "{channel}");
+		}
+	}
+
+	/// Chatters are known after their first message, per channel.
+	#[test]
+	fn chatters_are_known_after_their_first_message() {
+		let directory = std::env::temp_dir().join("twitch-chat-metrics-no-history");
+		let mut known_chatters = KnownChatters::new(directory);
+		assert_eq!(known_chatters.check_and_remember("a", "id-Alice", 10), None);
+		assert_eq!(known_chatters.check_and_remember("a", "id-Alice", 20), Some(10));
+		assert_eq!(known_chatters.check_and_remember("b", "id-Alice", 30), None);
+		assert_eq!(known_chatters.check_and_remember("a", "id-Bob", 40), None);
+		assert_eq!(known_chatters.check_and_remember("b", "id-Alice", 50), Some(30));
+	}
+
+	/// Older messages are paged in from the history, without the loaded ones of
+	/// the same second, and the chatters of the history are known since their
+	/// first message.
+	#[test]
+	fn older_messages_are_loaded() {
+		let directory = std::env::temp_dir()
+			.join(format!("twitch-chat-metrics-history-{}", std::process::id()));
+		let messages = [1000, 1001, 1002, 1002, 1003]
+			.into_iter()
+			.enumerate()
+			.map(|(index, timestamp)| {
+				Message::test("a", "Alice", &format!("message {index}"), timestamp)
+			})
+			.collect::<Vec<_>>();
+		let writer = HistoryWriter::spawn(&directory);
+		for message in &messages {
+			writer.write(ChatEvent::Message(message.clone()));
+		}
+		writer.finish();
+
+		let loaded = [messages[4].clone(), messages[3].clone()];
+		let older = OlderMessages::new(vec!["a".to_owned()], None, &loaded, 2).load(&directory);
+		let older_of_bob =
+			OlderMessages::new(vec!["a".to_owned()], Some("id-Bob".to_owned()), &loaded, 2)
+				.load(&directory);
+		let first_chatted =
+			KnownChatters::new(&directory).check_and_remember("a", "id-Alice", 2000);
+		fs::remove_dir_all(&directory).ok();
+
+		let older = older.expect("loading older messages");
+		assert_eq!(older, [messages[2].clone(), messages[1].clone()]);
+		assert!(older_of_bob.expect("loading older messages").is_empty());
+		assert_eq!(first_chatted, Some(1000));
+	}
+}
@@ -1,5 +1,19 @@
 //! The app's library.
 #![allow(clippy::expect_used)]
 
-mod chat;
+pub mod analytics;
+pub mod chat;
+pub mod giveaway;
+pub mod highlights;
+pub mod history;
+pub mod login;
+pub mod metrics;
+pub mod recording;
+pub mod session;
+pub mod settings;
+pub mod source;
+pub mod spam;
+pub mod terms;
+pub mod timeseries;
+#[cfg(feature = "gui")]
 pub mod ui;
@@ -0,0 +1,221 @@
+//! Login credentials for the chat connection. Secrets are kept in the system's
+//! keyring instead of the settings file.
+
+use std::fmt::{Debug, Display};
+
+use async_trait::async_trait;
+use chrono::Utc;
+use color_eyre::Result;
+use keyring::Entry;
+use twitch_irc::login::{TokenStorage, UserAccessToken};
+
+use crate::settings::Settings;
+
+/// Service name of the keyring entries.
+const KEYRING_SERVICE: &str = "twitch-chat-metrics";
+
+/// The keyring entry of the token of the given login name.
+fn token_entry(login: &str) -> Entry {
+	Entry::new(KEYRING_SERVICE, &format!("token:{login}"))
+}
+
+/// The keyring entry of the client secret of the given client ID.
+fn client_secret_entry(client_id: &str) -> Entry {
+	Entry::new(KEYRING_SERVICE, &format!("client-secret:{client_id}"))
+}
+
+/// Placeholder of a secret in debug output, empty if there is no secret.
+pub(crate) fn redact(secret: &str) -> &'static str {
+	if secret.is_empty() {
+		""
+	} else {
+		"<redacted>"
+	}
+}
+
+/// The token without the `oauth:` prefix it is usually pasted with, as the
+/// prefix is added when connecting.
+fn without_oauth_prefix(token: &str) -> String {
+	let token = token.trim();
+	token.strip_prefix("oauth:").unwrap_or(token).to_owned()
+}
+
+/// Store a new token for the login name in the keyring. With a refresh token,
+/// the token is marked as expired, so that it is refreshed on first use.
+pub fn store_token(login: &str, access_token: &str, refresh_token: &str) -> Result<()> {
+	let access_token = without_oauth_prefix(access_token);
+	let refresh_token = refresh_token.trim().to_owned();
+	let now = Utc::now();
+	let expires_at = (!refresh_token.is_empty()).then_some(now);
+	let token = UserAccessToken { access_token, refresh_token, created_at: now, expires_at };
+	token_entry(login).set_password(&serde_json::to_string(&token)?)?;
+	Ok(())
+}
+
+/// Store the client secret of the client ID in the keyring.
+pub fn store_client_secret(client_id: &str, client_secret: &str) -> Result<()> {
+	client_secret_entry(client_id).set_password(client_secret)?;
+	Ok(())
+}
+
+/// The login to connect to the chat with.
+#[derive(Clone)]
+pub enum Login {
+	/// Anonymous, read-only login.
+	Anonymous,
+	/// Login with a fixed OAuth token.
+	Static {
+		/// Login name of the account.
+		login: String,
+		/// OAuth access token.
+		token: String,
+	},
+	/// Login with an OAuth token that is refreshed when it expires.
+	Refreshing {
+		/// Login name of the account.
+		login: String,
+		/// Client ID of the twitch application.
+		client_id: String,
+		/// Client secret of the twitch application.
+		client_secret: String,
+		/// Storage of the token.
+		storage: KeyringTokenStorage,
+	},
+}
+
+impl Debug for Login {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Anonymous => f.write_str("Anonymous"),
+			Self::Static { login, token } => f
+				.debug_struct("Static")
+				.field("login", login)
+				.field("token", &redact(token))
+				.finish(),
+			Self::Refreshing { login, client_id, client_secret, storage } => f
+				.debug_struct("Refreshing")
+				.field("login", login)
+				.field("client_id", client_id)
+				.field("client_secret", &redact(client_secret))
+				.field("storage", storage)
+				.finish(),
+		}
+	}
+}
+
+impl Login {
+	/// Determine the login from the settings and the secrets in the keyring.
+	/// Falls back to the anonymous login if no token is stored.
+	pub fn from_settings(settings: &Settings) -> Result<Self> {
+		let login = settings.login_name.trim().to_lowercase();
+		if login.is_empty() {
+			return Ok(Self::Anonymous);
+		}
+
+		let token = match token_entry(&login).get_password() {
+			Ok(token) => serde_json::from_str::<UserAccessToken>(&token)?,
+			Err(keyring::Error::NoEntry) => {
+				tracing::warn!("No token stored for {login}, connecting anonymously");
+				return Ok(Self::Anonymous);
+			}
+			Err(err) => return Err(err.into()),
+		};
+
+		let client_id = settings.client_id.trim();
+		if !token.refresh_token.is_empty() && !client_id.is_empty() {
+			match client_secret_entry(client_id).get_password() {
+				Ok(client_secret) => {
+					return Ok(Self::Refreshing {
+						storage: KeyringTokenStorage { login: login.clone() },
+						login,
+						client_id: client_id.to_owned(),
+						client_secret,
+					});
+				}
+				Err(keyring::Error::NoEntry) => {
+					tracing::warn!("No client secret stored, token will not be refreshed");
+				}
+				Err(err) => return Err(err.into()),
+			}
+		}
+
+		// Tokens stored by older versions may still have the prefix.
+		Ok(Self::Static { login, token: without_oauth_prefix(&token.access_token) })
+	}
+}
+
+/// Token storage keeping the token in the keyring.
+#[derive(Debug, Clone)]
+pub struct KeyringTokenStorage {
+	/// Login name the token belongs to.
+	login: String,
+}
+
+#[async_trait]
+impl TokenStorage for KeyringTokenStorage {
+	type LoadError = TokenStorageError;
+	type UpdateError = TokenStorageError;
+
+	async fn load_token(&mut self) -> Result<UserAccessToken, Self::LoadError> {
+		let token = token_entry(&self.login).get_password()?;
+		Ok(serde_json::from_str(&token)?)
+	}
+
+	async fn update_token(&mut self, token: &UserAccessToken) -> Result<(), Self::UpdateError> {
+		token_entry(&self.login).set_password(&serde_json::to_string(token)?)?;
+		Ok(())
+	}
+}
+
+/// Error when loading or updating the token in the keyring.
+#[derive(Debug)]
+pub enum TokenStorageError {
+	/// Keyring access failed.
+	Keyring(keyring::Error),
+	/// Token (de-)serialization failed.
+	Json(serde_json::Error),
+}
+
+impl Display for TokenStorageError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Keyring(err) => f.write_fmt(format_args!("keyring error: {err}")),
+			Self::Json(err) => f.write_fmt(format_args!("token serialization error: {err}")),
+		}
+	}
+}
+
+impl std::error::Error for TokenStorageError {}
+
+impl From<keyring::Error> for TokenStorageError {
+	fn from(err: keyring::Error) -> Self {
+		Self::Keyring(err)
+	}
+}
+
+impl From<serde_json::Error> for TokenStorageError {
+	fn from(err: serde_json::Error) -> Self {
+		Self::Json(err)
+	}
+}
+
+/// Tests of the login credentials.
+#[cfg(test)]
+mod tests {
+	use super::{redact, without_oauth_prefix};
+
+	/// Pasted tokens are stored without the prefix and whitespace.
+	#[test]
+	fn oauth_prefix_is_removed() {
+		assert_eq!(without_oauth_prefix("oauth:abc123"), "abc123");
+		assert_eq!(without_oauth_prefix(" abc123\n"), "abc123");
+	}
+
+	/// Secrets are not shown in debug output.
+	#[test]
+	fn secrets_are_redacted() {
+		let login = super::Login::Static { login: "bot".to_owned(), token: "abc123".to_owned() };
+		assert!(!format!("{login:?}").contains("abc123"));
+		assert_eq!(redact(""), "");
+	}
+}
@@ -0,0 +1,670 @@
+//! Chat metrics, shared by the GUI and the headless collector.
+
+use std::cmp::Ordering;
+
+use im::{HashMap, HashSet, Vector};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	chat::{Ban, ChatEvent, ClearMessage, Message, UserNotice, UserNoticeEvent},
+	spam::SpamCounts,
+	terms::{TermFrequency, TrendingTerm},
+	timeseries::TimeSeries,
+};
+
+/// Number of entries in the top lists, e.g. of the top chatters.
+pub const TOP_COUNT: usize = 10;
+
+/// Metrics of all channels combined and of every channel on its own.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "gui", derive(druid::Data, druid::Lens))]
+pub struct ChatMetrics {
+	/// Metrics aggregated over all channels.
+	pub all: Metrics,
+	/// Metrics per channel.
+	pub channels: HashMap<String, Metrics>,
+}
+
+impl ChatMetrics {
+	/// Reset all the metrics.
+	pub fn reset(&mut self) {
+		self.all = Metrics::default();
+		self.channels.clear();
+	}
+
+	/// Summary of the metrics of all channels.
+	#[must_use]
+	pub fn summary(&self) -> MetricsSummary {
+		self.all.summary()
+	}
+
+	/// The metrics of the given channel, or of all channels if it is empty.
+	#[must_use]
+	pub fn channel(&self, channel: &str) -> Metrics {
+		if channel.is_empty() {
+			self.all.clone()
+		} else {
+			self.channels.get(channel).cloned().unwrap_or_default()
+		}
+	}
+
+	/// Update the metrics with any chat event.
+	pub fn add_event(&mut self, event: &ChatEvent) {
+		match event {
+			ChatEvent::Message(message) => self.add_message(message),
+			ChatEvent::ClearMessage(message) => self.add_clear_message(message),
+			ChatEvent::UserNotice(notice) => self.add_user_notice(notice),
+			ChatEvent::Ban(ban) => self.add_ban(ban),
+		}
+	}
+
+	/// Update the metrics with a new message.
+	pub fn add_message(&mut self, message: &Message) {
+		self.all.add_message(message);
+		self.channels.entry(message.channel.clone()).or_default().add_message(message);
+	}
+
+	/// Update the metrics with the terms of a new message.
+	pub fn add_terms(&mut self, message: &Message, terms: &[String]) {
+		self.all.add_terms(message.timestamp, terms);
+		self.channels
+			.entry(message.channel.clone())
+			.or_default()
+			.add_terms(message.timestamp, terms);
+	}
+
+	/// Recompute the trending terms of the metrics that got new terms since
+	/// the last time. This is throttled, as it goes through all recent terms.
+	pub fn update_trending_terms(&mut self) {
+		self.all.update_trending_terms();
+		let outdated: Vec<String> = self
+			.channels
+			.iter()
+			.filter(|(_, metrics)| metrics.trending_outdated)
+			.map(|(channel, _)| channel.clone())
+			.collect();
+		for channel in outdated {
+			if let Some(metrics) = self.channels.get_mut(&channel) {
+				metrics.update_trending_terms();
+			}
+		}
+	}
+
+	/// Update the metrics with a cleared message.
+	pub fn add_clear_message(&mut self, message: &ClearMessage) {
+		self.all.messages_cleared += 1;
+		self.channels.entry(message.channel.clone()).or_default().messages_cleared += 1;
+	}
+
+	/// Update the metrics with a new user notice.
+	pub fn add_user_notice(&mut self, notice: &UserNotice) {
+		self.all.add_user_notice(notice);
+		self.channels.entry(notice.channel.clone()).or_default().add_user_notice(notice);
+	}
+
+	/// Update the metrics with a new ban or timeout.
+	pub fn add_ban(&mut self, ban: &Ban) {
+		self.all.add_ban(ban);
+		self.channels.entry(ban.channel.clone()).or_default().add_ban(ban);
+	}
+}
+
+/// Chat metrics of one channel or all channels combined.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "gui", derive(druid::Data, druid::Lens))]
+pub struct Metrics {
+	/// Statistics of the chatters by user ID.
+	#[cfg_attr(feature = "gui", data(same_fn = "fast_chatters_same"))]
+	pub chatters: HashMap<String, ChatterStats>,
+	/// The top chatters by messages, updated with every message.
+	top_by_messages: Vector<ChatterStats>,
+	/// The top chatters by bits, updated with every message.
+	top_by_bits: Vector<ChatterStats>,
+	/// Number of chatters who did not chat in the channel before.
+	pub new_chatters: usize,
+	/// Number of chatters who sent their first message in the channel ever.
+	pub first_time_chatters: usize,
+	/// Number of chatters who chatted in the channel before.
+	pub returning_chatters: usize,
+	/// Number of total messages.
+	pub total_messages: usize,
+	/// Number of messages by subscribers.
+	pub subscriber_messages: usize,
+	/// Number of messages by VIPs.
+	pub vip_messages: usize,
+	/// Number of messages by moderators and the broadcaster.
+	pub moderator_messages: usize,
+	/// Number of total bits cheered.
+	pub total_bits: u64,
+	/// Number of messages cleared.
+	pub messages_cleared: usize,
+	/// Number of messages flagged as spam by kind.
+	pub spam: SpamCounts,
+	/// Number of new subscriptions.
+	pub new_subs: usize,
+	/// Number of renewed subscriptions.
+	pub resubs: usize,
+	/// Number of gifted subscriptions.
+	pub gifted_subs: usize,
+	/// Number of incoming raids.
+	pub raids: usize,
+	/// Number of viewers brought by raids.
+	pub raid_viewers: u64,
+	/// Number of announcements.
+	pub announcements: usize,
+	/// Number of users banned.
+	pub bans: usize,
+	/// Number of users timed out.
+	pub timeouts: usize,
+	/// Usage of the emotes by emote ID.
+	#[cfg_attr(feature = "gui", data(same_fn = "fast_emotes_same"))]
+	pub emotes: HashMap<String, EmoteStats>,
+	/// The most used emotes, updated with every message.
+	most_used_emotes: Vector<EmoteStats>,
+	/// The least used emotes, updated with every message.
+	least_used_emotes: Vector<EmoteStats>,
+	/// Activity per minute.
+	pub series: TimeSeries,
+	/// Frequency of the terms in the messages. It changes together with the
+	/// other metrics, so it is not compared.
+	#[cfg_attr(feature = "gui", data(ignore))]
+	pub terms: TermFrequency,
+	/// The terms trending in the last minutes, updated periodically.
+	trending_terms: Vector<TrendingTerm>,
+	/// Whether terms were added since the trending terms were updated.
+	#[cfg_attr(feature = "gui", data(ignore))]
+	trending_outdated: bool,
+}
+
+impl Metrics {
+	/// Summary of the metrics.
+	#[must_use]
+	pub fn summary(&self) -> MetricsSummary {
+		MetricsSummary {
+			unique_chatters: self.chatters.len(),
+			new_chatters: self.new_chatters,
+			first_time_chatters: self.first_time_chatters,
+			returning_chatters: self.returning_chatters,
+			total_messages: self.total_messages,
+			subscriber_messages: self.subscriber_messages,
+			vip_messages: self.vip_messages,
+			moderator_messages: self.moderator_messages,
+			total_bits: self.total_bits,
+			messages_cleared: self.messages_cleared,
+			spam_messages: self.spam.total(),
+			new_subs: self.new_subs,
+			resubs: self.resubs,
+			gifted_subs: self.gifted_subs,
+			raids: self.raids,
+			raid_viewers: self.raid_viewers,
+			announcements: self.announcements,
+			bans: self.bans,
+			timeouts: self.timeouts,
+		}
+	}
+
+	/// The top chatters, sorted by the given order.
+	#[must_use]
+	pub fn top_chatters(&self, order: ChatterOrder) -> &Vector<ChatterStats> {
+		match order {
+			ChatterOrder::Messages => &self.top_by_messages,
+			ChatterOrder::Bits => &self.top_by_bits,
+		}
+	}
+
+	/// The top emotes, sorted by the given order.
+	#[must_use]
+	pub fn top_emotes(&self, order: EmoteOrder) -> &Vector<EmoteStats> {
+		match order {
+			EmoteOrder::MostUsed => &self.most_used_emotes,
+			EmoteOrder::LeastUsed => &self.least_used_emotes,
+		}
+	}
+
+	/// The terms trending in the last minutes, most trending first.
+	#[must_use]
+	pub fn trending_terms(&self) -> &Vector<TrendingTerm> {
+		&self.trending_terms
+	}
+
+	/// Update the metrics with a new message.
+	fn add_message(&mut self, message: &Message) {
+		let chatter = self.chatters.entry(message.author.id.clone()).or_insert_with(|| {
+			if message.returning {
+				self.returning_chatters += 1;
+			} else {
+				self.new_chatters += 1;
+			}
+			if message.first_message {
+				self.first_time_chatters += 1;
+			}
+			ChatterStats::new(message)
+		});
+		chatter.add_message(message);
+		let chatter = chatter.clone();
+		update_top(&mut self.top_by_messages, &chatter, ChatterStats::same, |a, b| {
+			ChatterOrder::Messages.compare(a, b)
+		});
+		update_top(&mut self.top_by_bits, &chatter, ChatterStats::same, |a, b| {
+			ChatterOrder::Bits.compare(a, b)
+		});
+		self.total_messages += 1;
+		self.total_bits += message.bits.unwrap_or_default();
+		self.series.add_message(message);
+		let mut least_used_changed = false;
+		for emote in &message.emotes {
+			let stats = self
+				.emotes
+				.entry(emote.id.clone())
+				.or_insert_with(|| EmoteStats::new(emote.id.clone(), emote.code.clone()));
+			stats.add_use(&message.author.id);
+			let stats = stats.clone();
+			update_top(&mut self.most_used_emotes, &stats, EmoteStats::same, |a, b| {
+				EmoteOrder::MostUsed.compare(a, b)
+			});
+			// Uses only grow, so only new emotes and more uses of the listed ones
+			// change the least used emotes.
+			least_used_changed |= stats.uses == 1
+				|| self.least_used_emotes.iter().any(|least| EmoteStats::same(least, &stats));
+		}
+		if least_used_changed {
+			let mut emotes = self.emotes.values().collect::<Vec<_>>();
+			emotes.sort_by(|a, b| EmoteOrder::LeastUsed.compare(a, b));
+			self.least_used_emotes = emotes.into_iter().take(TOP_COUNT).cloned().collect();
+		}
+
+		if message.subscriber {
+			self.subscriber_messages += 1;
+		}
+		if message.is_vip() {
+			self.vip_messages += 1;
+		}
+		if message.is_moderator() {
+			self.moderator_messages += 1;
+		}
+		if let Some(kind) = message.spam {
+			self.spam.add(kind);
+		}
+	}
+
+	/// Update the metrics with the terms of a new message at the timestamp.
+	fn add_terms(&mut self, timestamp: i64, terms: &[String]) {
+		self.terms.add(timestamp, terms);
+		self.trending_outdated = true;
+	}
+
+	/// Recompute the trending terms if terms were added since the last time.
+	fn update_trending_terms(&mut self) {
+		if self.trending_outdated {
+			self.trending_terms = self.terms.trending(TOP_COUNT).into();
+			self.trending_outdated = false;
+		}
+	}
+
+	/// Update the metrics with a new user notice.
+	fn add_user_notice(&mut self, notice: &UserNotice) {
+		match notice.event {
+			UserNoticeEvent::Sub { .. } => {
+				self.new_subs += 1;
+				self.series.add_sub(notice.timestamp);
+			}
+			UserNoticeEvent::Resub { .. } => {
+				self.resubs += 1;
+				self.series.add_sub(notice.timestamp);
+			}
+			// Mass gifts are followed by the single gifts, so only those are counted.
+			UserNoticeEvent::SubGift { .. } => {
+				self.gifted_subs += 1;
+				self.series.add_sub(notice.timestamp);
+			}
+			UserNoticeEvent::Raid { viewers } => {
+				self.raids += 1;
+				self.raid_viewers += viewers;
+			}
+			UserNoticeEvent::Announcement => self.announcements += 1,
+			UserNoticeEvent::MassGift { .. } | UserNoticeEvent::Other(_) => {}
+		}
+	}
+
+	/// Update the metrics with a new ban or timeout.
+	fn add_ban(&mut self, ban: &Ban) {
+		if ban.duration.is_some() {
+			self.timeouts += 1;
+		} else {
+			self.bans += 1;
+		}
+	}
+}
+
+/// Statistics of a single chatter.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "gui", derive(druid::Data))]
+pub struct ChatterStats {
+	/// User ID of the chatter.
+	#[serde(skip)]
+	#[cfg_attr(feature = "gui", data(ignore))]
+	id: String,
+	/// Display name of the chatter.
+	pub name: String,
+	/// Number of messages sent.
+	pub messages: usize,
+	/// Number of bits cheered.
+	pub bits: u64,
+	/// Timestamp of the first message.
+	pub first_seen: i64,
+	/// Timestamp of the latest message.
+	pub last_seen: i64,
+}
+
+impl ChatterStats {
+	/// New statistics of the author of the message, without any messages yet.
+	fn new(message: &Message) -> Self {
+		Self {
+			id: message.author.id.clone(),
+			name: message.author.name.clone(),
+			messages: 0,
+			bits: 0,
+			first_seen: message.timestamp,
+			last_seen: message.timestamp,
+		}
+	}
+
+	/// Update the statistics with a new message.
+	fn add_message(&mut self, message: &Message) {
+		self.messages += 1;
+		self.bits += message.bits.unwrap_or_default();
+		self.first_seen = self.first_seen.min(message.timestamp);
+		self.last_seen = self.last_seen.max(message.timestamp);
+	}
+
+	/// Whether both are the statistics of the same chatter.
+	fn same(a: &Self, b: &Self) -> bool {
+		a.id == b.id
+	}
+}
+
+/// Usage statistics of a single emote.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "gui", derive(druid::Data))]
+pub struct EmoteStats {
+	/// ID of the emote.
+	#[serde(skip)]
+	#[cfg_attr(feature = "gui", data(ignore))]
+	id: String,
+	/// Code of the emote, i.e. its name.
+	pub code: String,
+	/// Number of times the emote was used.
+	pub uses: usize,
+	/// Number of unique users of the emote.
+	pub unique_users: usize,
+	/// IDs of the users of the emote.
+	#[serde(skip)]
+	#[cfg_attr(feature = "gui", data(ignore))]
+	users: HashSet<String>,
+}
+
+impl EmoteStats {
+	/// New statistics of an emote that was not used yet.
+	fn new(id: String, code: String) -> Self {
+		Self { id, code, uses: 0, unique_users: 0, users: HashSet::new() }
+	}
+
+	/// Whether both are the statistics of the same emote.
+	fn same(a: &Self, b: &Self) -> bool {
+		a.id == b.id
+	}
+
+	/// Update the statistics with a use by the user.
+	fn add_use(&mut self, user_id: &str) {
+		self.uses += 1;
+		if self.users.insert(user_id.to_owned()).is_none() {
+			self.unique_users += 1;
+		}
+	}
+}
+
+/// Order of the top chatters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "gui", derive(druid::Data))]
+pub enum ChatterOrder {
+	/// By number of messages.
+	#[default]
+	Messages,
+	/// By number of bits cheered.
+	Bits,
+}
+
+impl ChatterOrder {
+	/// Compare the chatters in this order, the top chatter first.
+	#[must_use]
+	pub fn compare(self, a: &ChatterStats, b: &ChatterStats) -> Ordering {
+		let ordering = match self {
+			Self::Messages => b.messages.cmp(&a.messages).then(b.bits.cmp(&a.bits)),
+			Self::Bits => b.bits.cmp(&a.bits).then(b.messages.cmp(&a.messages)),
+		};
+		ordering.then_with(|| a.name.cmp(&b.name))
+	}
+}
+
+/// Order of the top emotes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "gui", derive(druid::Data))]
+pub enum EmoteOrder {
+	/// Most used first.
+	#[default]
+	MostUsed,
+	/// Least used first, e.g. to find emotes nobody uses.
+	LeastUsed,
+}
+
+impl EmoteOrder {
+	/// Compare the emotes in this order, the top emote first.
+	#[must_use]
+	pub fn compare(self, a: &EmoteStats, b: &EmoteStats) -> Ordering {
+		let ordering = match self {
+			Self::MostUsed => b.uses.cmp(&a.uses),
+			Self::LeastUsed => a.uses.cmp(&b.uses),
+		};
+		ordering.then_with(|| a.code.cmp(&b.code))
+	}
+}
+
+/// Summary of the metrics, as stored with sessions.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "gui", derive(druid::Data))]
+pub struct MetricsSummary {
+	/// Number of unique chatters.
+	pub unique_chatters: usize,
+	/// Number of chatters who did not chat in the channel before.
+	#[serde(default)]
+	pub new_chatters: usize,
+	/// Number of chatters who sent their first message in the channel ever.
+	#[serde(default)]
+	pub first_time_chatters: usize,
+	/// Number of chatters who chatted in the channel before.
+	#[serde(default)]
+	pub returning_chatters: usize,
+	/// Number of total messages.
+	pub total_messages: usize,
+	/// Number of messages by subscribers.
+	pub subscriber_messages: usize,
+	/// Number of messages by VIPs.
+	#[serde(default)]
+	pub vip_messages: usize,
+	/// Number of messages by moderators and the broadcaster.
+	#[serde(default)]
+	pub moderator_messages: usize,
+	/// Number of total bits cheered.
+	pub total_bits: u64,
+	/// Number of messages cleared.
+	pub messages_cleared: usize,
+	/// Number of messages flagged as spam.
+	#[serde(default)]
+	pub spam_messages: usize,
+	/// Number of new subscriptions.
+	pub new_subs: usize,
+	/// Number of renewed subscriptions.
+	pub resubs: usize,
+	/// Number of gifted subscriptions.
+	pub gifted_subs: usize,
+	/// Number of incoming raids.
+	pub raids: usize,
+	/// Number of viewers brought by raids.
+	pub raid_viewers: u64,
+	/// Number of announcements.
+	pub announcements: usize,
+	/// Number of users banned.
+	pub bans: usize,
+	/// Number of users timed out.
+	pub timeouts: usize,
+}
+
+/// Update the entry in the sorted top list, keeping at most [`TOP_COUNT`]
+/// entries. Statistics only ever grow, so only the updated entry can move up
+/// in or into the list, which saves sorting all entries with every message.
+fn update_top<T: Clone>(
+	top: &mut Vector<T>,
+	entry: &T,
+	same: impl Fn(&T, &T) -> bool,
+	order: impl Fn(&T, &T) -> Ordering,
+) {
+	top.retain(|other| !same(entry, other));
+	let index = top.iter().position(|other| order(entry, other).is_lt()).unwrap_or(top.len());
+	if index < TOP_COUNT {
+		top.insert(index, entry.clone());
+		top.truncate(TOP_COUNT);
+	}
+}
+
+/// Simple fast comparison function for emotes. Emotes are only added and their
+/// statistics change together with the other metrics, so compare the length
+/// only.
+#[cfg(feature = "gui")]
+fn fast_emotes_same(a: &HashMap<String, EmoteStats>, b: &HashMap<String, EmoteStats>) -> bool {
+	a.len() == b.len()
+}
+
+/// Simple fast comparison function for chatters. Members are only added and
+/// their statistics change together with the other metrics, so compare the
+/// length only.
+#[cfg(feature = "gui")]
+fn fast_chatters_same(
+	a: &HashMap<String, ChatterStats>,
+	b: &HashMap<String, ChatterStats>,
+) -> bool {
+	a.len() == b.len()
+}
+
+/// Tests of the metrics.
+#[cfg(test)]
+mod tests {
+	use twitch_irc::message::Emote;
+
+	use super::{ChatMetrics, ChatterOrder, EmoteOrder, TOP_COUNT};
+	use crate::chat::Message;
+
+	/// Names of the top chatters of all channels in the order.
+	fn top_names(metrics: &ChatMetrics, order: ChatterOrder) -> Vec<String> {
+		metrics.all.top_chatters(order).iter().map(|chatter| chatter.name.clone()).collect()
+	}
+
+	/// The top chatters are kept sorted as chatters overtake each other, and
+	/// only the top ones are kept.
+	#[test]
+	fn top_chatters_are_updated() {
+		let mut metrics = ChatMetrics::default();
+		metrics.add_message(&Message::test("a", "Alice", "hello", 0));
+		metrics.add_message(&Message::test("a", "Bob", "hi", 1));
+		metrics.add_message(&Message::test("a", "Bob", "hi again", 2));
+		assert_eq!(top_names(&metrics, ChatterOrder::Messages), ["Bob", "Alice"]);
+
+		let mut cheer = Message::test("b", "Alice", "cheer100", 3);
+		cheer.bits = Some(100);
+		metrics.add_message(&cheer);
+		metrics.add_message(&Message::test("b", "Alice", "hello", 4));
+		assert_eq!(top_names(&metrics, ChatterOrder::Messages), ["Alice", "Bob"]);
+		assert_eq!(top_names(&metrics, ChatterOrder::Bits), ["Alice", "Bob"]);
+		assert_eq!(metrics.all.top_chatters(ChatterOrder::Messages)[0].messages, 3);
+		assert_eq!(metrics.all.top_chatters(ChatterOrder::Bits)[0].bits, 100);
+		assert_eq!(metrics.channel("b").top_chatters(ChatterOrder::Messages).len(), 1);
+
+		for index in 0..TOP_COUNT {
+			let name = format!("Chatter{index:02}");
+			metrics.add_message(&Message::test("a", &name, "one", 5));
+			metrics.add_message(&Message::test("a", &name, "two", 6));
+		}
+		let top = top_names(&metrics, ChatterOrder::Messages);
+		assert_eq!(top.len(), TOP_COUNT);
+		assert_eq!(top[0], "Alice");
+		assert_eq!(top[1], "Bob");
+		assert_eq!(top[2], "Chatter00");
+		assert!(!top.contains(&format!("Chatter{:02}", TOP_COUNT - 1)));
+	}
+
+	/// A message using the emotes, separated by spaces.
+	fn emote_message(user: &str, codes: &[&str]) -> Message {
+		let mut message = Message::test("a", user, &codes.join(" "), 0);
+		let mut start = 0;
+		for code in codes {
+			message.emotes.push(Emote {
+				id: format!("id-{code}"),
+				char_range: start..start + code.len(),
+				code: (*code).to_owned(),
+			});
+			start += code.len() + 1;
+		}
+		message
+	}
+
+	/// Codes of the emotes of all channels in the order.
+	fn emote_codes(metrics: &ChatMetrics, order: EmoteOrder) -> Vec<String> {
+		metrics.all.top_emotes(order).iter().map(|emote| emote.code.clone()).collect()
+	}
+
+	/// The most and least used emotes are kept sorted as emotes are used.
+	#[test]
+	fn top_emotes_are_updated() {
+		let mut metrics = ChatMetrics::default();
+		metrics.add_message(&emote_message("Alice", &["Kappa", "LUL", "PogChamp"]));
+		metrics.add_message(&emote_message("Bob", &["Kappa", "LUL"]));
+		metrics.add_message(&emote_message("Bob", &["Kappa"]));
+		assert_eq!(emote_codes(&metrics, EmoteOrder::MostUsed), ["Kappa", "LUL", "PogChamp"]);
+		assert_eq!(emote_codes(&metrics, EmoteOrder::LeastUsed), ["PogChamp", "LUL", "Kappa"]);
+		assert_eq!(metrics.all.top_emotes(EmoteOrder::MostUsed)[0].uses, 3);
+		assert_eq!(metrics.all.top_emotes(EmoteOrder::MostUsed)[0].unique_users, 2);
+
+		metrics.add_message(&emote_message("Carol", &["PogChamp", "PogChamp", "PogChamp"]));
+		metrics.add_message(&emote_message("Carol", &["BibleThump"]));
+		assert_eq!(
+			emote_codes(&metrics, EmoteOrder::MostUsed),
+			["PogChamp", "Kappa", "LUL", "BibleThump"]
+		);
+		assert_eq!(
+			emote_codes(&metrics, EmoteOrder::LeastUsed),
+			["BibleThump", "LUL", "Kappa", "PogChamp"]
+		);
+	}
+
+	/// The trending terms are updated with the terms added since the last
+	/// update, per channel and for all channels.
+	#[test]
+	fn trending_terms_are_updated() {
+		let mut metrics = ChatMetrics::default();
+		for (text, seconds) in [("hello", 0..10), ("pog", 600..603)] {
+			for second in seconds {
+				let message = Message::test("a", "Alice", text, second);
+				metrics.add_message(&message);
+				metrics.add_terms(&message, &[text.to_owned()]);
+			}
+		}
+		assert!(metrics.all.trending_terms().is_empty());
+
+		metrics.update_trending_terms();
+		let trending = metrics.all.trending_terms();
+		assert_eq!(trending.len(), 1);
+		assert_eq!(trending[0].term, "pog");
+		assert_eq!(trending[0].recent, 3);
+		assert_eq!(metrics.channel("a").trending_terms().len(), 1);
+		assert!(metrics.channel("b").trending_terms().is_empty());
+	}
+}
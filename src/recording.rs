@@ -0,0 +1,149 @@
+//! Recording of the raw chat messages and replaying of such recordings.
+
+use std::{
+	fs::{self, File},
+	io::{BufWriter, Write},
+	path::{Path, PathBuf},
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+use chrono::Local;
+use color_eyre::Result;
+use twitch_irc::message::{AsRawIRC, IRCMessage, ServerMessage};
+
+/// Directory the recordings are stored in.
+pub const DIRECTORY: &str = "recordings";
+
+/// Recorder of the raw IRC lines received from the server. Each line is
+/// prefixed with the unix timestamp in milliseconds it was received at.
+#[derive(Debug)]
+pub struct Recorder {
+	/// Writer to the recording file.
+	writer: BufWriter<File>,
+}
+
+impl Recorder {
+	/// Create a new recording file named after the current time in the given
+	/// directory.
+	pub fn create(directory: impl AsRef<Path>) -> Result<Self> {
+		let directory = directory.as_ref();
+		fs::create_dir_all(directory)?;
+		let path = directory.join(format!("{}.log", Local::now().format("%Y-%m-%d_%H-%M-%S")));
+		tracing::info!("Recording chat to {}", path.display());
+		Ok(Self { writer: BufWriter::new(File::create(path)?) })
+	}
+
+	/// Record a received message.
+	pub fn record(&mut self, message: &ServerMessage) -> Result<()> {
+		let received = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+		writeln!(self.writer, "{received} {}", message.source().as_raw_irc())?;
+		self.writer.flush()?;
+		Ok(())
+	}
+}
+
+/// Replay of a recording.
+#[derive(Debug, Clone)]
+pub struct Replay {
+	/// Path of the recording file.
+	pub file: PathBuf,
+	/// Speed to replay the recording at.
+	pub speed: ReplaySpeed,
+}
+
+/// Speed of a replay.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplaySpeed {
+	/// Replay with the original timing.
+	RealTime,
+	/// Replay with the original timing accelerated by the given factor.
+	Accelerated(f64),
+	/// Replay without waiting between messages.
+	AsFastAsPossible,
+}
+
+impl ReplaySpeed {
+	/// Get the replay speed from a factor, where 0 stands for as fast as
+	/// possible.
+	#[must_use]
+	pub fn from_factor(factor: f64) -> Self {
+		if factor <= 0.0 {
+			Self::AsFastAsPossible
+		} else if (factor - 1.0).abs() < f64::EPSILON {
+			Self::RealTime
+		} else {
+			Self::Accelerated(factor)
+		}
+	}
+
+	/// Time in milliseconds to replay a recorded time span of the given
+	/// milliseconds in.
+	#[must_use]
+	pub fn scale(self, millis: u64) -> u64 {
+		match self {
+			Self::RealTime => millis,
+			Self::Accelerated(factor) => (millis as f64 / factor) as u64,
+			Self::AsFastAsPossible => 0,
+		}
+	}
+}
+
+/// Parse a line of a recording into the time in milliseconds it was received
+/// at and the message.
+pub fn parse_line(line: &str) -> Result<(u64, ServerMessage)> {
+	let (received, raw) =
+		line.split_once(' ').ok_or_else(|| color_eyre::eyre::eyre!("Invalid recording line"))?;
+	let message = ServerMessage::try_from(IRCMessage::parse(raw)?)?;
+	Ok((received.parse()?, message))
+}
+
+/// Tests of the recordings.
+#[cfg(test)]
+mod tests {
+	use twitch_irc::message::ServerMessage;
+
+	use super::{parse_line, ReplaySpeed};
+	use crate::chat::ChatEvent;
+
+	/// Lines are parsed into the time received and the message.
+	#[test]
+	fn parse_valid_lines() {
+		let (received, message) =
+			parse_line("1660000000123 PING :tmi.twitch.tv").expect("valid ping");
+		assert_eq!(received, 1_660_000_000_123);
+		assert!(matches!(message, ServerMessage::Ping(_)));
+
+		let (_received, message) = parse_line(
+			"1660000000456 @badge-info=;badges=;color=;display-name=Alice;emotes=;id=abc;mod=0;\
+			 room-id=1;subscriber=0;tmi-sent-ts=1660000000000;turbo=0;user-id=42;user-type= \
+			 :alice!alice@alice.tmi.twitch.tv PRIVMSG #chan :hello world",
+		)
+		.expect("valid message");
+		match ChatEvent::from_server_message(message) {
+			Some(ChatEvent::Message(message)) => {
+				assert_eq!(message.channel, "chan");
+				assert_eq!(message.author.name, "Alice");
+				assert_eq!(message.message, "hello world");
+				assert_eq!(message.timestamp, 1_660_000_000);
+			}
+			event => panic!("Expected a message, got {event:?}"),
+		}
+	}
+
+	/// Lines without a valid time or message are rejected.
+	#[test]
+	fn parse_invalid_lines() {
+		assert!(parse_line("").is_err());
+		assert!(parse_line("PING :tmi.twitch.tv").is_err());
+		assert!(parse_line("abc PING :tmi.twitch.tv").is_err());
+		assert!(parse_line("1660000000123 PRIVMSG #chan").is_err());
+	}
+
+	/// Replay speeds scale the time between messages.
+	#[test]
+	fn replay_speed() {
+		assert_eq!(ReplaySpeed::from_factor(1.0).scale(1000), 1000);
+		assert_eq!(ReplaySpeed::from_factor(4.0).scale(1000), 250);
+		assert_eq!(ReplaySpeed::from_factor(0.0).scale(1000), 0);
+	}
+}
@@ -0,0 +1,151 @@
+//! Stream sessions, shared by the GUI and the headless collector.
+
+use std::{
+	fs::File,
+	path::{Path, PathBuf},
+	time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{Local, TimeZone};
+use color_eyre::Result;
+use im::Vector;
+use serde::{Deserialize, Serialize};
+
+use crate::metrics::MetricsSummary;
+
+/// The filename where the past sessions are stored.
+const STATE_FILE: &str = "sessions.json";
+
+/// The running session and the past sessions.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "gui", derive(druid::Data, druid::Lens))]
+pub struct Sessions {
+	/// Timestamp when the running session started, if any.
+	pub running_since: Option<i64>,
+	/// Past sessions, newest first.
+	pub past: Vector<Session>,
+	/// Time of the last chat activity in the running session.
+	#[cfg_attr(feature = "gui", data(ignore))]
+	last_activity: Option<Instant>,
+	/// Timestamp of the last chat activity in the running session.
+	#[cfg_attr(feature = "gui", data(ignore))]
+	last_timestamp: i64,
+	/// File the past sessions are stored in, if they are stored at all.
+	#[cfg_attr(feature = "gui", data(ignore))]
+	file: Option<PathBuf>,
+}
+
+impl Default for Sessions {
+	fn default() -> Self {
+		Self {
+			running_since: None,
+			past: Vector::new(),
+			last_activity: None,
+			last_timestamp: 0,
+			file: Some(STATE_FILE.into()),
+		}
+	}
+}
+
+impl Sessions {
+	/// Sessions that are kept in memory only, without loading or storing the
+	/// past sessions.
+	#[must_use]
+	pub fn in_memory() -> Self {
+		Self { file: None, ..Self::default() }
+	}
+
+	/// Load the past sessions from the file.
+	pub fn load(&mut self) -> Result<()> {
+		if let Some(file) = &self.file {
+			self.past = load_past(file)?.into();
+		}
+		Ok(())
+	}
+
+	/// Start a new session at the timestamp. The running session must have been
+	/// finished before.
+	pub fn start(&mut self, timestamp: i64) {
+		self.running_since = Some(timestamp);
+		self.last_activity = Some(Instant::now());
+		self.last_timestamp = timestamp;
+	}
+
+	/// Record chat activity at the timestamp.
+	pub fn activity(&mut self, timestamp: i64) {
+		self.last_activity = Some(Instant::now());
+		self.last_timestamp = timestamp;
+	}
+
+	/// Whether there was no chat activity for longer than the timeout.
+	#[must_use]
+	pub fn inactive_since(&self, now: Instant, timeout: Duration) -> bool {
+		self.last_activity.map_or(false, |last| now.saturating_duration_since(last) > timeout)
+	}
+
+	/// Finish the running session, if any, ending it at the timestamp or the
+	/// last activity if not given. The session is stored with the given
+	/// channels and totals.
+	pub fn finish(&mut self, ended: Option<i64>, channels: String, totals: MetricsSummary) {
+		if let Some(started) = self.running_since.take() {
+			let session = Session {
+				started,
+				ended: ended.unwrap_or(self.last_timestamp).max(started),
+				channels,
+				totals,
+			};
+			self.past.push_front(session);
+			if let Some(file) = &self.file {
+				if let Err(err) = save_past(file, &self.past) {
+					tracing::error!("Saving sessions failed: {err}");
+				}
+			}
+		}
+		self.last_activity = None;
+	}
+}
+
+/// A past stream session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "gui", derive(druid::Data))]
+pub struct Session {
+	/// Timestamp when the session started.
+	pub started: i64,
+	/// Timestamp when the session ended.
+	pub ended: i64,
+	/// The channels with chat activity during the session.
+	pub channels: String,
+	/// Metrics of all channels at the end of the session.
+	pub totals: MetricsSummary,
+}
+
+/// Load the past sessions from the file, newest first.
+fn load_past(path: &Path) -> Result<Vec<Session>> {
+	let file = File::open(path)?;
+	let past = serde_json::from_reader(file)?;
+	Ok(past)
+}
+
+/// Save the past sessions to the file, newest first.
+fn save_past<'a>(path: &Path, past: impl IntoIterator<Item = &'a Session>) -> Result<()> {
+	let file = File::create(path)?;
+	serde_json::to_writer_pretty(file, &past.into_iter().collect::<Vec<_>>())?;
+	Ok(())
+}
+
+/// Current unix timestamp.
+#[must_use]
+pub fn now() -> i64 {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map_or(0, |duration| duration.as_secs().try_into().unwrap_or(i64::MAX))
+}
+
+/// Format a unix timestamp as local date and time.
+#[must_use]
+pub fn format_time(timestamp: i64) -> String {
+	Local
+		.timestamp_opt(timestamp, 0)
+		.single()
+		.map_or_else(String::new, |time| time.format("%Y-%m-%d %H:%M").to_string())
+}
@@ -0,0 +1,240 @@
+//! The app's settings, shared by the GUI and the headless collector.
+
+use std::{fmt::Debug, fs::File};
+
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	login,
+	recording::{Replay, ReplaySpeed},
+};
+
+/// The Settings data + UI state.
+#[derive(Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "gui", derive(druid::Data, druid::Lens))]
+pub struct Settings {
+	/// Indicates whether a setting was edited and need to be saved.
+	#[serde(default, skip_serializing)]
+	pub changes: bool,
+	/// The twitch channel login names, separated by commas or whitespace.
+	#[serde(default, alias = "twitch_channel")]
+	pub twitch_channels: String,
+	/// The number of messages the chat should buffer.
+	#[serde(default = "Settings::default_chat_buffer")]
+	pub chat_buffer: usize,
+	/// Minutes of chat inactivity after which a session is stopped. Sessions
+	/// are started automatically by chat activity, unless this is 0.
+	#[serde(default = "Settings::default_session_timeout")]
+	pub session_timeout: u64,
+	/// Whether to store all chat events in the chat history databases.
+	#[serde(default = "Settings::default_store_history")]
+	pub store_history: bool,
+	/// Whether to record the raw chat messages to a file.
+	#[serde(default)]
+	pub record_chat: bool,
+	/// Recording file to replay instead of connecting to twitch, if not empty.
+	#[serde(default)]
+	pub replay_file: String,
+	/// Speed factor to replay the recording with, 0 for as fast as possible.
+	#[serde(default = "Settings::default_replay_speed")]
+	pub replay_speed: f64,
+	/// Words ignored by the term frequency analysis, separated by commas or
+	/// whitespace.
+	#[serde(default = "Settings::default_stop_words")]
+	pub stop_words: String,
+	/// Minimum number of characters of words in the term frequency analysis.
+	#[serde(default = "Settings::default_min_term_length")]
+	pub min_term_length: usize,
+	/// Factor the messages per minute have to exceed the moving average by to
+	/// be recorded as highlight, 0 to disable.
+	#[serde(default = "Settings::default_spike_factor")]
+	pub spike_factor: f64,
+	/// Address of an IRC server to connect to instead of twitch, e.g. a local
+	/// test server, if not empty.
+	#[serde(default)]
+	pub irc_server: String,
+	/// Login name of the bot account, empty to connect anonymously.
+	#[serde(default)]
+	pub login_name: String,
+	/// Client ID of the twitch application, required for refreshing tokens.
+	#[serde(default)]
+	pub client_id: String,
+	/// Newly entered OAuth access token. Only stored in the keyring.
+	#[serde(skip)]
+	pub oauth_token: String,
+	/// Newly entered OAuth refresh token. Only stored in the keyring.
+	#[serde(skip)]
+	pub refresh_token: String,
+	/// Newly entered client secret. Only stored in the keyring.
+	#[serde(skip)]
+	pub client_secret: String,
+}
+
+impl Default for Settings {
+	fn default() -> Self {
+		Self {
+			changes: false,
+			twitch_channels: String::new(),
+			chat_buffer: Self::default_chat_buffer(),
+			session_timeout: Self::default_session_timeout(),
+			store_history: Self::default_store_history(),
+			record_chat: false,
+			replay_file: String::new(),
+			replay_speed: Self::default_replay_speed(),
+			stop_words: Self::default_stop_words(),
+			min_term_length: Self::default_min_term_length(),
+			spike_factor: Self::default_spike_factor(),
+			irc_server: String::new(),
+			login_name: String::new(),
+			client_id: String::new(),
+			oauth_token: String::new(),
+			refresh_token: String::new(),
+			client_secret: String::new(),
+		}
+	}
+}
+
+impl Debug for Settings {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Settings")
+			.field("changes", &self.changes)
+			.field("twitch_channels", &self.twitch_channels)
+			.field("chat_buffer", &self.chat_buffer)
+			.field("session_timeout", &self.session_timeout)
+			.field("store_history", &self.store_history)
+			.field("record_chat", &self.record_chat)
+			.field("replay_file", &self.replay_file)
+			.field("replay_speed", &self.replay_speed)
+			.field("stop_words", &self.stop_words)
+			.field("min_term_length", &self.min_term_length)
+			.field("spike_factor", &self.spike_factor)
+			.field("irc_server", &self.irc_server)
+			.field("login_name", &self.login_name)
+			.field("client_id", &self.client_id)
+			.field("oauth_token", &login::redact(&self.oauth_token))
+			.field("refresh_token", &login::redact(&self.refresh_token))
+			.field("client_secret", &login::redact(&self.client_secret))
+			.finish()
+	}
+}
+
+impl Settings {
+	/// The filename where the settings file is stored.
+	const STATE_FILE: &'static str = "settings.json";
+
+	/// Load the settings from the file.
+	pub fn from_file() -> Result<Self> {
+		let file = File::open(Self::STATE_FILE)?;
+		let state = serde_json::from_reader(file)?;
+		Ok(state)
+	}
+
+	/// Save the settings to the file. Newly entered secrets are moved to the
+	/// keyring instead.
+	pub fn save(&mut self) -> Result<()> {
+		let login = self.login_name.trim().to_lowercase();
+		if !login.is_empty() && (!self.oauth_token.is_empty() || !self.refresh_token.is_empty()) {
+			login::store_token(
+				&login,
+				&std::mem::take(&mut self.oauth_token),
+				&std::mem::take(&mut self.refresh_token),
+			)?;
+		}
+		if !self.client_secret.is_empty() {
+			login::store_client_secret(self.client_id.trim(), &self.client_secret)?;
+			self.client_secret.clear();
+		}
+
+		let file = File::create(Self::STATE_FILE)?;
+		serde_json::to_writer_pretty(file, self)?;
+		Ok(())
+	}
+
+	/// The list of distinct, lowercase twitch channel login names to join.
+	#[must_use]
+	pub fn channels(&self) -> Vec<String> {
+		let mut channels = Vec::new();
+		for channel in self.twitch_channels.split(|c: char| c == ',' || c.is_whitespace()) {
+			let channel = channel.trim_start_matches('#').to_lowercase();
+			if !channel.is_empty() && !channels.contains(&channel) {
+				channels.push(channel);
+			}
+		}
+		channels
+	}
+
+	/// The recording to replay instead of connecting to twitch, if any.
+	#[must_use]
+	pub fn replay(&self) -> Option<Replay> {
+		let file = self.replay_file.trim();
+		(!file.is_empty()).then(|| Replay {
+			file: file.into(),
+			speed: ReplaySpeed::from_factor(self.replay_speed),
+		})
+	}
+
+	/// Whether the chat history is stored and looked up. Replays are not stored
+	/// again, and the chat of another IRC server, e.g. a test server, does not
+	/// belong to the twitch channels' history.
+	#[must_use]
+	pub fn uses_history(&self) -> bool {
+		self.store_history && self.replay().is_none() && self.irc_server.trim().is_empty()
+	}
+
+	/// Settings default value for `chat_buffer`.
+	fn default_chat_buffer() -> usize {
+		250
+	}
+
+	/// Settings default value for `session_timeout`.
+	fn default_session_timeout() -> u64 {
+		30
+	}
+
+	/// Settings default value for `store_history`.
+	fn default_store_history() -> bool {
+		true
+	}
+
+	/// Settings default value for `stop_words`.
+	fn default_stop_words() -> String {
+		"the, and, you, your, that, this, for, are, was, with, have, not, but, what, all, can, \
+		 just, get, its, it's, i'm, don't, like, she, him, her, they, them, our, out, one, now, \
+		 how, why, who, too, yes, has, had, his, from, will, would, there, then, than, lol"
+			.to_owned()
+	}
+
+	/// Settings default value for `min_term_length`.
+	fn default_min_term_length() -> usize {
+		3
+	}
+
+	/// Settings default value for `spike_factor`.
+	fn default_spike_factor() -> f64 {
+		2.0
+	}
+
+	/// Settings default value for `replay_speed`.
+	fn default_replay_speed() -> f64 {
+		1.0
+	}
+}
+
+/// Tests of the settings.
+#[cfg(test)]
+mod tests {
+	use super::Settings;
+
+	/// The history is only used for the live twitch chat.
+	#[test]
+	fn history_is_used_for_twitch_only() {
+		assert!(Settings::default().uses_history());
+		assert!(!Settings { store_history: false, ..Settings::default() }.uses_history());
+		assert!(
+			!Settings { replay_file: "chat.log".to_owned(), ..Settings::default() }.uses_history()
+		);
+		assert!(!Settings { irc_server: "localhost:6667".to_owned(), ..Settings::default() }
+			.uses_history());
+	}
+}
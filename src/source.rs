@@ -0,0 +1,505 @@
+//! Sources of chat events, like the live twitch chat or replayed recordings.
+
+use std::{
+	collections::VecDeque,
+	fmt::Debug,
+	time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use tokio::{
+	fs::File,
+	io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines},
+	net::{
+		tcp::{OwnedReadHalf, OwnedWriteHalf},
+		TcpStream,
+	},
+	sync::mpsc::UnboundedReceiver,
+};
+use twitch_irc::{
+	login::{LoginCredentials, RefreshingLoginCredentials, StaticLoginCredentials},
+	message::{IRCMessage, ServerMessage},
+	ClientConfig, SecureTCPTransport, TwitchIRCClient,
+};
+
+use crate::{
+	chat::{ChatEvent, ConnectionStatus},
+	login::Login,
+	recording::{self, Recorder, Replay},
+	settings::Settings,
+};
+
+/// Event yielded by a chat source.
+#[derive(Debug, Clone)]
+pub enum SourceEvent {
+	/// Normalized chat event.
+	Chat(ChatEvent),
+	/// Update of the source's connection status.
+	Status(ConnectionStatus),
+}
+
+/// Source of chat events.
+#[async_trait]
+pub trait ChatSource: Debug + Send {
+	/// Wait for the next event. Returns `None` once the source is exhausted.
+	async fn next_event(&mut self) -> Option<SourceEvent>;
+}
+
+/// Create the source configured in the settings: the replay of a recording,
+/// a plain IRC server or the live twitch chat.
+#[must_use]
+pub fn from_settings(settings: &Settings) -> Box<dyn ChatSource> {
+	let channels = settings.channels();
+	if let Some(replay) = settings.replay() {
+		Box::new(ReplaySource::new(replay))
+	} else if !settings.irc_server.trim().is_empty() {
+		Box::new(IrcServerSource::new(settings.irc_server.trim().to_owned(), channels))
+	} else {
+		let login = Login::from_settings(settings).unwrap_or_else(|err| {
+			tracing::error!("Loading login credentials failed: {err}");
+			Login::Anonymous
+		});
+		let recorder = if settings.record_chat {
+			Recorder::create(recording::DIRECTORY)
+				.map_err(|err| tracing::error!("Creating chat recording failed: {err}"))
+				.ok()
+		} else {
+			None
+		};
+		twitch(channels, login, recorder)
+	}
+}
+
+/// Create the source for the live twitch chat of the given channels.
+#[must_use]
+pub fn twitch(
+	channels: Vec<String>,
+	login: Login,
+	recorder: Option<Recorder>,
+) -> Box<dyn ChatSource> {
+	match login {
+		Login::Anonymous => Box::new(TwitchSource::new(
+			channels,
+			recorder,
+			ClientConfig::<StaticLoginCredentials>::default,
+		)),
+		Login::Static { login, token } => {
+			let credentials = StaticLoginCredentials::new(login, Some(token));
+			Box::new(TwitchSource::new(channels, recorder, move || {
+				ClientConfig::new_simple(credentials.clone())
+			}))
+		}
+		Login::Refreshing { login, client_id, client_secret, storage } => {
+			Box::new(TwitchSource::new(channels, recorder, move || {
+				ClientConfig::new_simple(RefreshingLoginCredentials::init_with_username(
+					Some(login.clone()),
+					client_id.clone(),
+					client_secret.clone(),
+					storage.clone(),
+				))
+			}))
+		}
+	}
+}
+
+/// Source of the live twitch chat. Reconnects with a new client and increasing
+/// backoff if the channels could not be joined for some time.
+pub struct TwitchSource<L: LoginCredentials> {
+	/// The twitch channels to join to.
+	channels: Vec<String>,
+	/// Recorder of the raw messages, if enabled.
+	recorder: Option<Recorder>,
+	/// Function creating the client configuration for each connection.
+	config: Box<dyn Fn() -> ClientConfig<L> + Send>,
+	/// The current connection, if any.
+	connection: Option<TwitchConnection<L>>,
+	/// Number of the current reconnect attempt.
+	attempt: u32,
+	/// Whether connecting failed permanently.
+	failed: bool,
+}
+
+impl<L: LoginCredentials> Debug for TwitchSource<L> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("TwitchSource")
+			.field("channels", &self.channels)
+			.field("attempt", &self.attempt)
+			.field("failed", &self.failed)
+			.finish_non_exhaustive()
+	}
+}
+
+/// A connection of the twitch client.
+struct TwitchConnection<L: LoginCredentials> {
+	/// Receiver of the server's messages.
+	messages: UnboundedReceiver<ServerMessage>,
+	/// The client.
+	client: TwitchIRCClient<SecureTCPTransport, L>,
+	/// Interval to check whether all channels are joined.
+	check_interval: tokio::time::Interval,
+	/// Time since when not all channels are joined.
+	not_joined_since: Option<Instant>,
+}
+
+/// Event of a twitch connection.
+enum ConnectionEvent {
+	/// Message from the server.
+	Message(ServerMessage),
+	/// Result of checking whether all channels are joined.
+	JoinCheck(bool),
+	/// The connection was closed.
+	Closed,
+}
+
+impl<L: LoginCredentials> TwitchConnection<L> {
+	/// Wait for the next event of the connection.
+	async fn next(&mut self, channels: &[String]) -> ConnectionEvent {
+		tokio::select! {
+			message = self.messages.recv() => {
+				message.map_or(ConnectionEvent::Closed, ConnectionEvent::Message)
+			}
+			_ = self.check_interval.tick() => {
+				let mut joined = true;
+				for channel in channels {
+					let (_wanted, channel_joined) =
+						self.client.get_channel_status(channel.clone()).await;
+					joined &= channel_joined;
+				}
+				ConnectionEvent::JoinCheck(joined)
+			}
+		}
+	}
+}
+
+impl<L: LoginCredentials> TwitchSource<L> {
+	/// Interval to check whether all channels are joined.
+	const JOIN_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+	/// Time after which a connection not joined to all channels is considered
+	/// lost.
+	const JOIN_TIMEOUT: Duration = Duration::from_secs(30);
+	/// Maximum time to wait before reconnecting.
+	const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+	/// Create a new source, using the given function to create the client
+	/// configuration for each connection.
+	pub fn new(
+		channels: Vec<String>,
+		recorder: Option<Recorder>,
+		config: impl Fn() -> ClientConfig<L> + Send + 'static,
+	) -> Self {
+		Self {
+			channels,
+			recorder,
+			config: Box::new(config),
+			connection: None,
+			attempt: 0,
+			failed: false,
+		}
+	}
+
+	/// Connect with a new client, after waiting for the backoff if this is a
+	/// reconnect. Returns a status update if there is one to report.
+	async fn connect(&mut self) -> Option<ConnectionStatus> {
+		if self.attempt > 0 {
+			let backoff = Self::MAX_BACKOFF.min(Duration::from_secs(1 << self.attempt.min(6)));
+			tracing::warn!("Chat connection lost, reconnecting in {backoff:?}");
+			tokio::time::sleep(backoff).await;
+		}
+
+		let (messages, client) = TwitchIRCClient::<SecureTCPTransport, L>::new((self.config)());
+		for channel in &self.channels {
+			if let Err(err) = client.join(channel.clone()) {
+				self.failed = true;
+				return Some(ConnectionStatus::Failed(format!(
+					"Invalid channel name `{channel}`: {err}"
+				)));
+			}
+		}
+
+		self.connection = Some(TwitchConnection {
+			messages,
+			client,
+			check_interval: tokio::time::interval(Self::JOIN_CHECK_INTERVAL),
+			not_joined_since: Some(Instant::now()),
+		});
+		(self.attempt == 0).then_some(ConnectionStatus::Connecting)
+	}
+
+	/// Drop the connection to reconnect and report it.
+	fn connection_lost(&mut self) -> ConnectionStatus {
+		self.connection = None;
+		self.attempt += 1;
+		ConnectionStatus::Reconnecting { attempt: self.attempt }
+	}
+
+	/// Record the raw message, if enabled.
+	fn record(&mut self, message: &ServerMessage) {
+		if let Some(recorder) = &mut self.recorder {
+			if let Err(err) = recorder.record(message) {
+				tracing::error!("Recording message failed, stopping recording: {err}");
+				self.recorder = None;
+			}
+		}
+	}
+}
+
+#[async_trait]
+impl<L: LoginCredentials> ChatSource for TwitchSource<L> {
+	async fn next_event(&mut self) -> Option<SourceEvent> {
+		loop {
+			if self.failed {
+				return None;
+			}
+
+			let connection = match &mut self.connection {
+				Some(connection) => connection,
+				None => {
+					if let Some(status) = self.connect().await {
+						return Some(SourceEvent::Status(status));
+					}
+					continue;
+				}
+			};
+
+			match connection.next(&self.channels).await {
+				ConnectionEvent::Message(ServerMessage::Notice(notice))
+					if notice.channel_login.is_none() =>
+				{
+					// Global notices are only sent on login failures.
+					self.failed = true;
+					return Some(SourceEvent::Status(ConnectionStatus::Failed(
+						notice.message_text,
+					)));
+				}
+				ConnectionEvent::Message(message) => {
+					self.record(&message);
+					if let Some(event) = ChatEvent::from_server_message(message) {
+						return Some(SourceEvent::Chat(event));
+					}
+				}
+				ConnectionEvent::JoinCheck(joined) => match (joined, connection.not_joined_since) {
+					(true, Some(_)) => {
+						connection.not_joined_since = None;
+						self.attempt = 0;
+						return Some(SourceEvent::Status(ConnectionStatus::Joined));
+					}
+					(false, None) => {
+						// Twitch's client rejoins on its own, reported as the
+						// attempt following the last one.
+						connection.not_joined_since = Some(Instant::now());
+						return Some(SourceEvent::Status(ConnectionStatus::Reconnecting {
+							attempt: self.attempt + 1,
+						}));
+					}
+					(false, Some(since)) if since.elapsed() > Self::JOIN_TIMEOUT => {
+						return Some(SourceEvent::Status(self.connection_lost()));
+					}
+					_ => {}
+				},
+				ConnectionEvent::Closed => {
+					return Some(SourceEvent::Status(self.connection_lost()))
+				}
+			}
+		}
+	}
+}
+
+/// Source replaying a recording.
+#[derive(Debug)]
+pub struct ReplaySource {
+	/// The replay to run.
+	replay: Replay,
+	/// Lines of the recording, once opened.
+	lines: Option<Lines<BufReader<File>>>,
+	/// Time the replay started.
+	start: Instant,
+	/// Time in milliseconds the first replayed message was received at.
+	first_received: Option<u64>,
+	/// Whether the replay finished.
+	finished: bool,
+}
+
+impl ReplaySource {
+	/// Create a new source for the replay.
+	#[must_use]
+	pub fn new(replay: Replay) -> Self {
+		Self { replay, lines: None, start: Instant::now(), first_received: None, finished: false }
+	}
+}
+
+#[async_trait]
+impl ChatSource for ReplaySource {
+	async fn next_event(&mut self) -> Option<SourceEvent> {
+		if self.finished {
+			return None;
+		}
+
+		let file = self.replay.file.display().to_string();
+		let lines = match &mut self.lines {
+			Some(lines) => lines,
+			None => {
+				let status = match File::open(&self.replay.file).await {
+					Ok(recording) => {
+						self.lines = Some(BufReader::new(recording).lines());
+						self.start = Instant::now();
+						ConnectionStatus::Replaying(file)
+					}
+					Err(err) => {
+						self.finished = true;
+						ConnectionStatus::Failed(format!("Opening {file}: {err}"))
+					}
+				};
+				return Some(SourceEvent::Status(status));
+			}
+		};
+
+		loop {
+			let line = match lines.next_line().await {
+				Ok(Some(line)) => line,
+				Ok(None) => {
+					self.finished = true;
+					return Some(SourceEvent::Status(ConnectionStatus::ReplayFinished(file)));
+				}
+				Err(err) => {
+					self.finished = true;
+					return Some(SourceEvent::Status(ConnectionStatus::Failed(format!(
+						"Reading {file}: {err}"
+					))));
+				}
+			};
+
+			let (received, message) = match recording::parse_line(&line) {
+				Ok(parsed) => parsed,
+				Err(err) => {
+					tracing::warn!("Skipping invalid recording line: {err}");
+					continue;
+				}
+			};
+
+			// Wait until the message is due according to the replay speed.
+			let first = *self.first_received.get_or_insert(received);
+			let delay = self.replay.speed.scale(received.saturating_sub(first));
+			tokio::time::sleep_until((self.start + Duration::from_millis(delay)).into()).await;
+
+			if let Some(event) = ChatEvent::from_server_message(message) {
+				return Some(SourceEvent::Chat(event));
+			}
+		}
+	}
+}
+
+/// Source connecting to a plain IRC server speaking twitch's protocol, like a
+/// local fake server for development and testing.
+#[derive(Debug)]
+pub struct IrcServerSource {
+	/// Address of the server.
+	address: String,
+	/// The channels to join to.
+	channels: Vec<String>,
+	/// The connection's lines and writer, once connected.
+	connection: Option<(Lines<BufReader<OwnedReadHalf>>, OwnedWriteHalf)>,
+	/// Whether the connection ended.
+	finished: bool,
+}
+
+impl IrcServerSource {
+	/// Create a new source for the server at the given address.
+	#[must_use]
+	pub fn new(address: String, channels: Vec<String>) -> Self {
+		Self { address, channels, connection: None, finished: false }
+	}
+
+	/// Connect to the server and join the channels anonymously.
+	async fn connect(&self) -> std::io::Result<(Lines<BufReader<OwnedReadHalf>>, OwnedWriteHalf)> {
+		let (reader, mut writer) = TcpStream::connect(&self.address).await?.into_split();
+		let mut login =
+			"CAP REQ :twitch.tv/tags twitch.tv/commands\r\nNICK justinfan12345\r\n".to_owned();
+		for channel in &self.channels {
+			login.push_str(&format!("JOIN #{channel}\r\n"));
+		}
+		writer.write_all(login.as_bytes()).await?;
+		Ok((BufReader::new(reader).lines(), writer))
+	}
+}
+
+#[async_trait]
+impl ChatSource for IrcServerSource {
+	async fn next_event(&mut self) -> Option<SourceEvent> {
+		if self.finished {
+			return None;
+		}
+
+		let (lines, writer) = match &mut self.connection {
+			Some(connection) => connection,
+			None => {
+				let status = match self.connect().await {
+					Ok(connection) => {
+						self.connection = Some(connection);
+						ConnectionStatus::Joined
+					}
+					Err(err) => {
+						self.finished = true;
+						ConnectionStatus::Failed(format!("Connecting to {}: {err}", self.address))
+					}
+				};
+				return Some(SourceEvent::Status(status));
+			}
+		};
+
+		loop {
+			let line = match lines.next_line().await {
+				Ok(Some(line)) => line,
+				Ok(None) => {
+					self.finished = true;
+					return Some(SourceEvent::Status(ConnectionStatus::Failed(
+						"Connection closed by the server".to_owned(),
+					)));
+				}
+				Err(err) => {
+					self.finished = true;
+					return Some(SourceEvent::Status(ConnectionStatus::Failed(err.to_string())));
+				}
+			};
+
+			let message = match IRCMessage::parse(&line).map(ServerMessage::try_from) {
+				Ok(Ok(message)) => message,
+				_ => {
+					tracing::warn!("Skipping invalid IRC line: {line}");
+					continue;
+				}
+			};
+
+			if let ServerMessage::Ping(_) = message {
+				writer.write_all(b"PONG :tmi.twitch.tv\r\n").await.ok();
+			} else if let Some(event) = ChatEvent::from_server_message(message) {
+				return Some(SourceEvent::Chat(event));
+			}
+		}
+	}
+}
+
+/// In-memory source of predefined events, e.g. for tests.
+#[derive(Debug, Clone, Default)]
+pub struct MemorySource {
+	/// The events still to yield.
+	events: VecDeque<SourceEvent>,
+}
+
+impl FromIterator<SourceEvent> for MemorySource {
+	fn from_iter<T: IntoIterator<Item = SourceEvent>>(iter: T) -> Self {
+		Self { events: iter.into_iter().collect() }
+	}
+}
+
+impl FromIterator<ChatEvent> for MemorySource {
+	fn from_iter<T: IntoIterator<Item = ChatEvent>>(iter: T) -> Self {
+		iter.into_iter().map(SourceEvent::Chat).collect()
+	}
+}
+
+#[async_trait]
+impl ChatSource for MemorySource {
+	async fn next_event(&mut self) -> Option<SourceEvent> {
+		self.events.pop_front()
+	}
+}
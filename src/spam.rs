@@ -0,0 +1,282 @@
+//! Detection of spam in chat: repeated messages, copypastas, excessive caps and
+//! emote-only messages.
+
+use std::{
+	collections::{HashSet, VecDeque},
+	fmt::Display,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::chat::Message;
+
+/// Kind of spam a message was flagged as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "gui", derive(druid::Data))]
+pub enum SpamKind {
+	/// The same user sent the same message repeatedly.
+	Repeated,
+	/// Several users sent the same long message.
+	Copypasta,
+	/// The message is mostly in capital letters.
+	Caps,
+	/// The message consists of many emotes only.
+	EmoteOnly,
+}
+
+impl Display for SpamKind {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Repeated => f.write_str("repeated"),
+			Self::Copypasta => f.write_str("copypasta"),
+			Self::Caps => f.write_str("caps"),
+			Self::EmoteOnly => f.write_str("emote-only"),
+		}
+	}
+}
+
+/// Number of spam messages by kind.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "gui", derive(druid::Data))]
+pub struct SpamCounts {
+	/// Number of repeated messages.
+	pub repeated: usize,
+	/// Number of copypasta messages.
+	pub copypasta: usize,
+	/// Number of messages with excessive caps.
+	pub caps: usize,
+	/// Number of emote-only messages.
+	pub emote_only: usize,
+}
+
+impl SpamCounts {
+	/// Count a spam message of the kind.
+	pub fn add(&mut self, kind: SpamKind) {
+		match kind {
+			SpamKind::Repeated => self.repeated += 1,
+			SpamKind::Copypasta => self.copypasta += 1,
+			SpamKind::Caps => self.caps += 1,
+			SpamKind::EmoteOnly => self.emote_only += 1,
+		}
+	}
+
+	/// Total number of spam messages.
+	#[must_use]
+	pub fn total(&self) -> usize {
+		self.repeated + self.copypasta + self.caps + self.emote_only
+	}
+}
+
+/// A recent message, remembered to detect repetitions.
+#[derive(Debug, Clone)]
+struct RecentMessage {
+	/// Login name of the channel the message was sent in.
+	channel: String,
+	/// Timestamp when the message was sent.
+	timestamp: i64,
+	/// ID of the sender.
+	user_id: String,
+	/// Normalized message text.
+	text: String,
+}
+
+/// Detector of spam in the stream of messages.
+#[derive(Debug, Default)]
+pub struct SpamDetector {
+	/// Messages of the last seconds, oldest first.
+	recent: VecDeque<RecentMessage>,
+}
+
+impl SpamDetector {
+	/// Seconds in which repetitions are detected.
+	const WINDOW: i64 = 60;
+	/// Maximum number of recent messages remembered.
+	const MAX_RECENT: usize = 1000;
+	/// Number of earlier identical messages of a user to be repeated.
+	const MIN_REPETITIONS: usize = 2;
+	/// Minimum number of characters of a copypasta.
+	const MIN_COPYPASTA_LENGTH: usize = 20;
+	/// Number of other users sending the same message to be a copypasta.
+	const MIN_COPYPASTA_USERS: usize = 2;
+	/// Minimum number of letters to check for excessive caps.
+	const MIN_CAPS_LETTERS: usize = 10;
+	/// Percentage of capital letters to be excessive.
+	const CAPS_PERCENTAGE: usize = 70;
+	/// Minimum number of emotes of emote-only spam.
+	const MIN_EMOTES: usize = 5;
+
+	/// Check the new message for spam.
+	pub fn detect(&mut self, message: &Message) -> Option<SpamKind> {
+		while self
+			.recent
+			.front()
+			.map_or(false, |recent| recent.timestamp < message.timestamp - Self::WINDOW)
+		{
+			self.recent.pop_front();
+		}
+
+		let text = normalize(&message.message);
+		let kind = self
+			.repetition(message, &text)
+			.or_else(|| excessive_caps(message))
+			.or_else(|| emote_only(message));
+
+		if !text.is_empty() {
+			self.recent.push_back(RecentMessage {
+				channel: message.channel.clone(),
+				timestamp: message.timestamp,
+				user_id: message.author.id.clone(),
+				text,
+			});
+			if self.recent.len() > Self::MAX_RECENT {
+				self.recent.pop_front();
+			}
+		}
+
+		kind
+	}
+
+	/// Check whether the normalized message text was recently sent repeatedly
+	/// by the same user or by several users.
+	fn repetition(&self, message: &Message, text: &str) -> Option<SpamKind> {
+		if text.is_empty() {
+			return None;
+		}
+
+		let mut repetitions = 0;
+		let mut other_users = HashSet::new();
+		for recent in self
+			.recent
+			.iter()
+			.filter(|recent| recent.channel == message.channel && recent.text == text)
+		{
+			if recent.user_id == message.author.id {
+				repetitions += 1;
+			} else {
+				other_users.insert(recent.user_id.as_str());
+			}
+		}
+
+		if repetitions >= Self::MIN_REPETITIONS {
+			Some(SpamKind::Repeated)
+		} else if text.chars().count() >= Self::MIN_COPYPASTA_LENGTH
+			&& other_users.len() >= Self::MIN_COPYPASTA_USERS
+		{
+			Some(SpamKind::Copypasta)
+		} else {
+			None
+		}
+	}
+}
+
+/// Normalize the message text, so that near-identical messages are equal:
+/// lowercase words without punctuation, separated by single spaces.
+fn normalize(text: &str) -> String {
+	text.split(|c: char| !c.is_alphanumeric())
+		.filter(|word| !word.is_empty())
+		.map(str::to_lowercase)
+		.collect::<Vec<_>>()
+		.join(" ")
+}
+
+/// Check whether the message text without emotes is mostly in capital letters.
+fn excessive_caps(message: &Message) -> Option<SpamKind> {
+	let text = message.text_without_emotes();
+	let letters = text.chars().filter(|c| c.is_alphabetic()).count();
+	let capitals = text.chars().filter(|c| c.is_uppercase()).count();
+	(letters >= SpamDetector::MIN_CAPS_LETTERS
+		&& capitals * 100 >= letters * SpamDetector::CAPS_PERCENTAGE)
+		.then_some(SpamKind::Caps)
+}
+
+/// Check whether the message consists of many emotes only.
+fn emote_only(message: &Message) -> Option<SpamKind> {
+	(message.emotes.len() >= SpamDetector::MIN_EMOTES
+		&& message.text_without_emotes().trim().is_empty())
+	.then_some(SpamKind::EmoteOnly)
+}
+
+/// Tests of the spam detection.
+#[cfg(test)]
+mod tests {
+	use twitch_irc::message::Emote;
+
+	use super::{SpamDetector, SpamKind};
+	use crate::chat::Message;
+
+	/// The same user sending the same message is repeated spam from the third
+	/// time within the window, ignoring case and punctuation.
+	#[test]
+	fn repeated_messages() {
+		let mut detector = SpamDetector::default();
+		assert_eq!(detector.detect(&Message::test("a", "Alice", "buy followers", 0)), None);
+		assert_eq!(detector.detect(&Message::test("a", "Alice", "Buy followers!", 10)), None);
+		assert_eq!(
+			detector.detect(&Message::test("a", "Alice", "BUY  followers", 20)),
+			Some(SpamKind::Repeated)
+		);
+		// In another channel, the messages are counted separately.
+		assert_eq!(detector.detect(&Message::test("b", "Alice", "buy followers", 30)), None);
+	}
+
+	/// Repetitions outside of the window do not count.
+	#[test]
+	fn repetitions_expire() {
+		let mut detector = SpamDetector::default();
+		for timestamp in [0, 40, 120] {
+			assert_eq!(detector.detect(&Message::test("a", "Alice", "hello", timestamp)), None);
+		}
+	}
+
+	/// The same long message sent by several users is a copypasta, short ones
+	/// are not.
+	#[test]
+	fn copypasta() {
+		let text = "this is a rather long copypasta message";
+		let mut detector = SpamDetector::default();
+		assert_eq!(detector.detect(&Message::test("a", "Alice", text, 0)), None);
+		assert_eq!(detector.detect(&Message::test("a", "Bob", text, 1)), None);
+		assert_eq!(
+			detector.detect(&Message::test("a", "Carol", text, 2)),
+			Some(SpamKind::Copypasta)
+		);
+
+		for (user, timestamp) in [("Alice", 3), ("Bob", 4), ("Carol", 5)] {
+			assert_eq!(detector.detect(&Message::test("a", user, "gg", timestamp)), None);
+		}
+	}
+
+	/// Messages mostly in capital letters are caps spam, short ones are not.
+	#[test]
+	fn excessive_caps() {
+		let mut detector = SpamDetector::default();
+		assert_eq!(
+			detector.detect(&Message::test("a", "Alice", "WHY IS NOBODY LISTENING", 0)),
+			Some(SpamKind::Caps)
+		);
+		assert_eq!(detector.detect(&Message::test("a", "Bob", "LOL OK", 1)), None);
+		assert_eq!(
+			detector.detect(&Message::test("a", "Carol", "Visit NASA and the ESA today", 2)),
+			None
+		);
+	}
+
+	/// Messages of many emotes only are emote-only spam.
+	#[test]
+	fn emote_only() {
+		let mut message = Message::test("a", "Alice", "Kappa Kappa Kappa Kappa Kappa", 0);
+		message.emotes = (0..5)
+			.map(|i| Emote {
+				id: "25".to_owned(),
+				char_range: i * 6..i * 6 + 5,
+				code: "Kappa".to_owned(),
+			})
+			.collect();
+		let mut detector = SpamDetector::default();
+		assert_eq!(detector.detect(&message), Some(SpamKind::EmoteOnly));
+
+		message.message.push_str(" nice");
+		message.emotes.pop();
+		assert_eq!(detector.detect(&message), None);
+	}
+}
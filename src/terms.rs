@@ -0,0 +1,220 @@
+//! Frequency analysis of the words and phrases in chat.
+
+use std::collections::HashSet;
+
+use im::{HashMap, Vector};
+use serde::Serialize;
+
+use crate::{chat::Message, settings::Settings};
+
+/// Filter extracting the terms of messages: words and pairs of consecutive
+/// words, without emotes, stop words and too short words.
+#[derive(Debug, Clone, Default)]
+pub struct TermFilter {
+	/// Minimum number of characters of a word.
+	min_length: usize,
+	/// Lowercase words to ignore.
+	stop_words: HashSet<String>,
+}
+
+impl TermFilter {
+	/// Create the filter configured in the settings.
+	#[must_use]
+	pub fn from_settings(settings: &Settings) -> Self {
+		Self {
+			min_length: settings.min_term_length,
+			stop_words: settings
+				.stop_words
+				.split(|c: char| c == ',' || c.is_whitespace())
+				.filter(|word| !word.is_empty())
+				.map(str::to_lowercase)
+				.collect(),
+		}
+	}
+
+	/// The distinct terms of the message.
+	#[must_use]
+	pub fn terms(&self, message: &Message) -> Vec<String> {
+		let text = message.text_without_emotes();
+		let words = text
+			.split(|c: char| !c.is_alphanumeric() && c != '\'')
+			.map(str::to_lowercase)
+			.filter(|word| word.chars().count() >= self.min_length.max(1))
+			.filter(|word| !self.stop_words.contains(word))
+			.collect::<Vec<_>>();
+
+		let mut terms = words.windows(2).map(|pair| pair.join(" ")).collect::<Vec<_>>();
+		terms.extend(words);
+		terms.sort();
+		terms.dedup();
+		terms
+	}
+}
+
+/// A term that is used more in the last minutes than during the session.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "gui", derive(druid::Data))]
+pub struct TrendingTerm {
+	/// The term.
+	pub term: String,
+	/// Number of messages with the term in the last minutes.
+	pub recent: usize,
+	/// How many times more frequent the term is in the last minutes than
+	/// during the rest of the session.
+	pub factor: f64,
+}
+
+/// Frequency of the terms during the session and in the last minutes.
+#[derive(Debug, Clone, Default)]
+pub struct TermFrequency {
+	/// Number of messages with the term during the session.
+	counts: HashMap<String, usize>,
+	/// Number of terms counted during the session.
+	total: usize,
+	/// Number of messages with the term per minute in the last minutes, oldest
+	/// first.
+	recent: Vector<(i64, HashMap<String, usize>)>,
+}
+
+impl TermFrequency {
+	/// Number of minutes that count as recent.
+	pub const RECENT_MINUTES: i64 = 5;
+	/// Minimum number of recent messages with a term to be trending.
+	const MIN_RECENT: usize = 3;
+	/// Minimum factor of the recent frequency over the session's to be
+	/// trending.
+	const MIN_FACTOR: f64 = 2.0;
+
+	/// Add the terms of a message at the timestamp.
+	pub fn add(&mut self, timestamp: i64, terms: &[String]) {
+		for term in terms {
+			*self.counts.entry(term.clone()).or_default() += 1;
+		}
+		self.total += terms.len();
+
+		let minute = timestamp.div_euclid(60);
+		if self.recent.back().map_or(true, |(last, _)| *last < minute) {
+			self.recent.push_back((minute, HashMap::new()));
+			while self
+				.recent
+				.front()
+				.map_or(false, |(first, _)| *first <= minute - Self::RECENT_MINUTES)
+			{
+				self.recent.pop_front();
+			}
+		}
+		if let Some((_, counts)) = self.recent.iter_mut().find(|(bucket, _)| *bucket == minute) {
+			for term in terms {
+				*counts.entry(term.clone()).or_default() += 1;
+			}
+		}
+	}
+
+	/// Number of messages per term in the minute, if it is one of the last
+	/// ones.
+	#[must_use]
+	pub fn minute_counts(&self, minute: i64) -> Option<&HashMap<String, usize>> {
+		self.recent.iter().find(|(bucket, _)| *bucket == minute).map(|(_, counts)| counts)
+	}
+
+	/// The terms trending in the last minutes, most trending first.
+	#[must_use]
+	pub fn trending(&self, count: usize) -> Vec<TrendingTerm> {
+		let mut recent = HashMap::<&str, usize>::new();
+		for (_, counts) in &self.recent {
+			for (term, messages) in counts {
+				*recent.entry(term.as_str()).or_default() += messages;
+			}
+		}
+		let recent_total: usize = recent.values().sum();
+		let baseline_total = self.total.saturating_sub(recent_total);
+
+		let mut trending = recent
+			.into_iter()
+			.filter(|(_, recent)| *recent >= Self::MIN_RECENT)
+			.map(|(term, recent)| {
+				let baseline =
+					self.counts.get(term).copied().unwrap_or_default().saturating_sub(recent);
+				// Add one to the baseline to not divide by zero for new terms.
+				let recent_share = recent as f64 / recent_total as f64;
+				let baseline_share = (baseline + 1) as f64 / (baseline_total + 1) as f64;
+				TrendingTerm {
+					term: term.to_owned(),
+					recent,
+					factor: recent_share / baseline_share,
+				}
+			})
+			.filter(|term| term.factor >= Self::MIN_FACTOR)
+			.collect::<Vec<_>>();
+		trending.sort_by(|a, b| b.factor.total_cmp(&a.factor).then_with(|| a.term.cmp(&b.term)));
+		trending.truncate(count);
+		trending
+	}
+}
+
+/// Tests of the term frequency analysis.
+#[cfg(test)]
+mod tests {
+	use super::TermFrequency;
+
+	/// The terms as a list of strings.
+	fn terms(terms: &[&str]) -> Vec<String> {
+		terms.iter().map(|term| (*term).to_owned()).collect()
+	}
+
+	/// Terms used much more in the last minutes than before are trending,
+	/// common and rarely used terms are not.
+	#[test]
+	fn trending_terms() {
+		let mut frequency = TermFrequency::default();
+		for second in 0..20 {
+			frequency.add(second, &terms(&["hello", "stream"]));
+		}
+		for second in 600..603 {
+			frequency.add(second, &terms(&["pog", "hello"]));
+		}
+		frequency.add(610, &terms(&["clutch"]));
+		frequency.add(611, &terms(&["clutch"]));
+
+		let trending = frequency.trending(10);
+		let names = trending.iter().map(|term| term.term.as_str()).collect::<Vec<_>>();
+		assert_eq!(names, ["pog"]);
+		assert_eq!(trending[0].recent, 3);
+		assert!(trending[0].factor >= 2.0);
+	}
+
+	/// Only the requested number of terms is returned, most trending first.
+	#[test]
+	fn trending_terms_are_limited() {
+		let mut frequency = TermFrequency::default();
+		for second in 0..10 {
+			frequency.add(second, &terms(&["hello"]));
+		}
+		for second in 600..605 {
+			frequency.add(second, &terms(&["pog"]));
+		}
+		for second in 605..608 {
+			frequency.add(second, &terms(&["clutch"]));
+		}
+
+		let trending = frequency.trending(1);
+		assert_eq!(trending.len(), 1);
+		assert_eq!(trending[0].term, "pog");
+	}
+
+	/// Minutes older than the recent ones are forgotten.
+	#[test]
+	fn old_minutes_are_not_recent() {
+		let mut frequency = TermFrequency::default();
+		frequency.add(0, &terms(&["hello"]));
+		assert!(frequency.minute_counts(0).is_some());
+		frequency.add(TermFrequency::RECENT_MINUTES * 60, &terms(&["hello"]));
+		assert!(frequency.minute_counts(0).is_none());
+		assert_eq!(
+			frequency
+				.minute_counts(TermFrequency::RECENT_MINUTES)
+				.and_then(|counts| counts.get("hello")),
+			Some(&1)
+		);
+	}
+}
@@ -0,0 +1,225 @@
+//! Time series of chat activity in buckets of one minute.
+
+use im::{HashMap, HashSet, Vector};
+
+use crate::chat::Message;
+
+/// Activity of chat in one minute.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "gui", derive(druid::Data))]
+pub struct Bucket {
+	/// Minute since the unix epoch.
+	pub minute: i64,
+	/// Number of messages.
+	pub messages: usize,
+	/// Number of unique chatters.
+	pub unique_chatters: usize,
+	/// Number of bits cheered.
+	pub bits: u64,
+	/// Number of emotes used.
+	pub emotes: usize,
+	/// Number of new, renewed and gifted subscriptions.
+	pub subs: usize,
+	/// IDs of the chatters.
+	#[cfg_attr(feature = "gui", data(ignore))]
+	chatters: HashSet<String>,
+	/// Number of uses per emote code.
+	#[cfg_attr(feature = "gui", data(ignore))]
+	emote_uses: HashMap<String, usize>,
+}
+
+impl Bucket {
+	/// New empty bucket of the minute.
+	fn new(minute: i64) -> Self {
+		Self {
+			minute,
+			messages: 0,
+			unique_chatters: 0,
+			bits: 0,
+			emotes: 0,
+			subs: 0,
+			chatters: HashSet::new(),
+			emote_uses: HashMap::new(),
+		}
+	}
+
+	/// Number of uses per emote code.
+	#[must_use]
+	pub fn emote_uses(&self) -> &HashMap<String, usize> {
+		&self.emote_uses
+	}
+}
+
+/// Window of the time series to look at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "gui", derive(druid::Data))]
+pub enum SeriesWindow {
+	/// The last 10 minutes.
+	#[default]
+	TenMinutes,
+	/// The last hour.
+	OneHour,
+	/// The whole session.
+	Session,
+}
+
+impl SeriesWindow {
+	/// Number of minutes in the window, `None` for the whole session.
+	#[must_use]
+	pub fn minutes(self) -> Option<usize> {
+		match self {
+			Self::TenMinutes => Some(10),
+			Self::OneHour => Some(60),
+			Self::Session => None,
+		}
+	}
+}
+
+/// Time series of consecutive buckets of one minute, up to the latest event.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "gui", derive(druid::Data))]
+pub struct TimeSeries {
+	/// The buckets, oldest first.
+	buckets: Vector<Bucket>,
+}
+
+impl TimeSeries {
+	/// Maximum number of buckets kept, which is two days.
+	const MAX_BUCKETS: usize = 2 * 24 * 60;
+
+	/// The buckets, oldest first.
+	#[must_use]
+	pub fn buckets(&self) -> &Vector<Bucket> {
+		&self.buckets
+	}
+
+	/// The bucket of the timestamp, adding buckets up to it if it is newer than
+	/// the latest. `None` if it is too old.
+	fn bucket_mut(&mut self, timestamp: i64) -> Option<&mut Bucket> {
+		let minute = timestamp.div_euclid(60);
+		match self.buckets.back().map(|bucket| bucket.minute) {
+			Some(last) if minute <= last => {}
+			Some(last)
+				if usize::try_from(minute - last).map_or(false, |gap| gap <= Self::MAX_BUCKETS) =>
+			{
+				for minute in last + 1..=minute {
+					self.buckets.push_back(Bucket::new(minute));
+				}
+				while self.buckets.len() > Self::MAX_BUCKETS {
+					self.buckets.pop_front();
+				}
+			}
+			_ => {
+				self.buckets.clear();
+				self.buckets.push_back(Bucket::new(minute));
+			}
+		}
+
+		let first = self.buckets.front()?.minute;
+		let index = usize::try_from(minute - first).ok()?;
+		self.buckets.get_mut(index)
+	}
+
+	/// Add a message.
+	pub fn add_message(&mut self, message: &Message) {
+		if let Some(bucket) = self.bucket_mut(message.timestamp) {
+			bucket.messages += 1;
+			bucket.bits += message.bits.unwrap_or_default();
+			bucket.emotes += message.emotes.len();
+			for emote in &message.emotes {
+				*bucket.emote_uses.entry(emote.code.clone()).or_default() += 1;
+			}
+			if bucket.chatters.insert(message.author.id.clone()).is_none() {
+				bucket.unique_chatters += 1;
+			}
+		}
+	}
+
+	/// Add a subscription.
+	pub fn add_sub(&mut self, timestamp: i64) {
+		if let Some(bucket) = self.bucket_mut(timestamp) {
+			bucket.subs += 1;
+		}
+	}
+
+	/// The buckets in the window, oldest first.
+	pub fn window(&self, window: SeriesWindow) -> impl ExactSizeIterator<Item = &Bucket> {
+		let skip = window.minutes().map_or(0, |minutes| self.buckets.len().saturating_sub(minutes));
+		self.buckets.iter().skip(skip)
+	}
+
+	/// Maximum of the value of the buckets in the window, at least zero.
+	#[must_use]
+	pub fn max(&self, window: SeriesWindow, value: impl Fn(&Bucket) -> f64) -> f64 {
+		self.window(window).map(value).fold(0.0, f64::max)
+	}
+}
+
+/// Tests of the time series.
+#[cfg(test)]
+mod tests {
+	use super::{SeriesWindow, TimeSeries};
+	use crate::chat::Message;
+
+	/// Minutes and messages of the buckets, oldest first.
+	fn buckets(series: &TimeSeries) -> Vec<(i64, usize)> {
+		series.buckets().iter().map(|bucket| (bucket.minute, bucket.messages)).collect()
+	}
+
+	/// Gaps are filled with empty buckets, and late messages are added to their
+	/// minute.
+	#[test]
+	fn gaps_are_filled() {
+		let mut series = TimeSeries::default();
+		series.add_message(&Message::test("a", "Alice", "hello", 600));
+		series.add_message(&Message::test("a", "Bob", "hi", 785));
+		assert_eq!(buckets(&series), [(10, 1), (11, 0), (12, 0), (13, 1)]);
+
+		series.add_message(&Message::test("a", "Carol", "late", 670));
+		series.add_sub(730);
+		assert_eq!(buckets(&series), [(10, 1), (11, 1), (12, 0), (13, 1)]);
+		assert_eq!(series.buckets()[2].subs, 1);
+	}
+
+	/// Messages older than the first bucket are dropped.
+	#[test]
+	fn too_old_messages_are_dropped() {
+		let mut series = TimeSeries::default();
+		series.add_message(&Message::test("a", "Alice", "hello", 600));
+		series.add_message(&Message::test("a", "Bob", "old", 540));
+		assert_eq!(buckets(&series), [(10, 1)]);
+	}
+
+	/// A gap longer than the kept buckets starts the series anew, and the
+	/// number of buckets is limited.
+	#[test]
+	fn long_gaps_restart_the_series() {
+		let mut series = TimeSeries::default();
+		let max = i64::try_from(TimeSeries::MAX_BUCKETS).expect("bucket limit fits");
+		series.add_message(&Message::test("a", "Alice", "hello", 0));
+		series.add_message(&Message::test("a", "Alice", "later", (max + 1) * 60));
+		assert_eq!(buckets(&series), [(max + 1, 1)]);
+
+		series.add_message(&Message::test("a", "Alice", "much later", (2 * max + 1) * 60));
+		assert_eq!(series.buckets().len(), TimeSeries::MAX_BUCKETS);
+		assert_eq!(series.buckets().front().map(|bucket| bucket.minute), Some(max + 2));
+		assert_eq!(series.buckets().back().map(|bucket| bucket.messages), Some(1));
+	}
+
+	/// The window holds the latest buckets, or all of them for the session.
+	#[test]
+	fn window_holds_the_latest_buckets() {
+		let mut series = TimeSeries::default();
+		series.add_message(&Message::test("a", "Alice", "hello", 0));
+		series.add_message(&Message::test("a", "Alice", "hi", 60 * 60));
+		series.add_message(&Message::test("a", "Bob", "hi", 60 * 60));
+
+		let minutes =
+			|window| series.window(window).map(|bucket| bucket.minute).collect::<Vec<_>>();
+		assert_eq!(minutes(SeriesWindow::TenMinutes), (51..=60).collect::<Vec<_>>());
+		assert_eq!(minutes(SeriesWindow::OneHour), (1..=60).collect::<Vec<_>>());
+		assert_eq!(minutes(SeriesWindow::Session), (0..=60).collect::<Vec<_>>());
+		let max = series.max(SeriesWindow::TenMinutes, |bucket| bucket.messages as f64);
+		assert!((max - 2.0).abs() < f64::EPSILON);
+	}
+}
@@ -1,20 +1,26 @@
 //! UI part of the app.
 
-use druid::{AppLauncher, Data, Lens, LocalizedString, WindowDesc};
+use druid::{im::Vector, AppLauncher, Data, Lens, LocalizedString, WindowDesc};
+
+use crate::{analytics::Analytics, chat::ConnectionStatus, settings::Settings};
 
 pub mod widgets;
 
 /// The root UI data/state.
 #[derive(Debug, Clone, Default, Data, Lens)]
 pub struct UIState {
+	/// The channels currently joined.
+	pub channels: Vector<String>,
+	/// Status of the chat connection.
+	pub connection: ConnectionStatus,
+	/// State of the analytics engine, observed by the widgets.
+	pub analytics: Analytics,
 	/// Overview data/state.
 	pub overview: widgets::overview::Overview,
 	/// Chat data/state.
 	pub chat: widgets::chat::Chat,
-	/// Giveaway data/state.
-	pub giveaway: widgets::giveaway::Giveaway,
 	/// Settings data/state.
-	pub settings: widgets::settings::Settings,
+	pub settings: Settings,
 }
 
 /// Get the window launcher for this UI.
@@ -0,0 +1,53 @@
+//! Channel selector used by the tabs.
+
+use druid::{
+	im::Vector,
+	lens,
+	widget::{Button, Flex, Label, List},
+	Lens, LensExt, Widget, WidgetExt,
+};
+
+use crate::ui::UIState;
+
+/// Check whether the channel selection includes the given channel. An empty
+/// selection stands for all channels.
+#[must_use]
+pub fn selects(selection: &str, channel: &str) -> bool {
+	selection.is_empty() || selection == channel
+}
+
+/// The channel selector widget. Offers all joined channels plus the combined
+/// "all channels" option and writes the choice into the given lens.
+#[must_use]
+pub fn widget<L>(selection: L) -> impl Widget<UIState>
+where
+	L: Lens<UIState, String> + Clone + 'static,
+{
+	let get_selection = selection.clone();
+	let options = List::new(|| {
+		Button::dynamic(|(_selected, channel): &(String, String), _env| {
+			if channel.is_empty() {
+				"All channels".to_owned()
+			} else {
+				channel.clone()
+			}
+		})
+		.disabled_if(|(selected, channel): &(String, String), _env| selected == channel)
+		.on_click(|_ctx, (selected, channel): &mut (String, String), _env| {
+			*selected = channel.clone();
+		})
+	})
+	.horizontal()
+	.lens(lens::Map::new(
+		move |data: &UIState| {
+			let mut options = data.channels.clone();
+			options.push_front(String::new());
+			(get_selection.get(data), options)
+		},
+		move |data: &mut UIState, (selected, _options): (String, Vector<String>)| {
+			selection.put(data, selected);
+		},
+	));
+
+	Flex::row().with_child(Label::new("Channel: ")).with_child(options)
+}
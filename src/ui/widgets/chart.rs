@@ -0,0 +1,110 @@
+//! Line chart widget.
+
+use druid::{
+	kurbo::BezPath, BoxConstraints, Color, Data, Env, Event, EventCtx, LayoutCtx, LifeCycle,
+	LifeCycleCtx, PaintCtx, Point, Rect, RenderContext, Size, UpdateCtx, Widget,
+};
+
+use crate::timeseries::{Bucket, SeriesWindow, TimeSeries};
+
+/// Widget drawing one value of the buckets of a time series in a window as a
+/// line chart, scaled to the maximum value. The values are read from the
+/// buckets while painting.
+#[derive(Debug, Clone)]
+pub struct LineChart {
+	/// Color of the line.
+	color: Color,
+	/// The value of a bucket.
+	value: fn(&Bucket) -> f64,
+}
+
+impl LineChart {
+	/// Height of the chart.
+	const HEIGHT: f64 = 80.0;
+	/// Width of the chart if it is not constrained.
+	const DEFAULT_WIDTH: f64 = 300.0;
+
+	/// New line chart of the value drawn in the color.
+	#[must_use]
+	pub fn new(color: Color, value: fn(&Bucket) -> f64) -> Self {
+		Self { color, value }
+	}
+}
+
+impl Widget<(TimeSeries, SeriesWindow)> for LineChart {
+	fn event(
+		&mut self,
+		_ctx: &mut EventCtx,
+		_event: &Event,
+		_data: &mut (TimeSeries, SeriesWindow),
+		_env: &Env,
+	) {
+	}
+
+	fn lifecycle(
+		&mut self,
+		_ctx: &mut LifeCycleCtx,
+		_event: &LifeCycle,
+		_data: &(TimeSeries, SeriesWindow),
+		_env: &Env,
+	) {
+	}
+
+	fn update(
+		&mut self,
+		ctx: &mut UpdateCtx,
+		old_data: &(TimeSeries, SeriesWindow),
+		data: &(TimeSeries, SeriesWindow),
+		_env: &Env,
+	) {
+		if !old_data.same(data) {
+			ctx.request_paint();
+		}
+	}
+
+	fn layout(
+		&mut self,
+		_ctx: &mut LayoutCtx,
+		bc: &BoxConstraints,
+		_data: &(TimeSeries, SeriesWindow),
+		_env: &Env,
+	) -> Size {
+		let width = bc.max().width;
+		let width = if width.is_finite() { width } else { Self::DEFAULT_WIDTH };
+		bc.constrain(Size::new(width, Self::HEIGHT))
+	}
+
+	fn paint(
+		&mut self,
+		ctx: &mut PaintCtx,
+		(series, window): &(TimeSeries, SeriesWindow),
+		_env: &Env,
+	) {
+		let size = ctx.size();
+		let bounds = Rect::from_origin_size(Point::ORIGIN, size);
+		ctx.stroke(bounds, &Color::GRAY, 1.0);
+
+		let buckets = series.window(*window);
+		let count = buckets.len();
+		let max = series.max(*window, self.value);
+		if count < 2 || max <= 0.0 {
+			return;
+		}
+
+		let step = size.width / (count - 1) as f64;
+		let mut path = BezPath::new();
+		for (i, bucket) in buckets.enumerate() {
+			let point =
+				Point::new(i as f64 * step, size.height * (1.0 - (self.value)(bucket) / max));
+			if i == 0 {
+				path.move_to(point);
+			} else {
+				path.line_to(point);
+			}
+		}
+		ctx.with_save(|ctx| {
+			ctx.clip(bounds);
+			ctx.stroke(path, &self.color, 2.0);
+		});
+	}
+}
@@ -2,18 +2,28 @@
 
 use druid::{
 	im::Vector,
-	widget::{Label, LineBreaking, List, Scroll},
-	Data, Insets, Lens, TextAlignment, Widget, WidgetExt,
+	lens, theme,
+	widget::{Either, Flex, Label, LineBreaking, List, Scroll, SizedBox},
+	Color, Data, Insets, Lens, LensExt, TextAlignment, Widget, WidgetExt,
 };
 
-use crate::{chat::Message, ui::UIState};
+use super::{channel_select, scrollback::Scrollback};
+use crate::{chat::Message, history::OlderMessages, ui::UIState};
 
 /// Chat UI data/state.
 #[derive(Debug, Clone, Default, Data, Lens)]
 pub struct Chat {
-	/// Buffer of chat messages.
+	/// Selected channel to show the chat of, empty for all channels.
+	pub channel: String,
+	/// Buffer of chat messages of all channels.
 	#[data(same_fn = "fast_messages_same")]
 	pub messages: Vector<Message>,
+	/// Number of older messages paged in from the chat history, which are kept
+	/// in addition to the chat buffer.
+	pub scrollback: usize,
+	/// Counter of in-place changes to the messages, which the fast comparison
+	/// does not notice.
+	pub revision: usize,
 }
 
 /// Fast way to make sure two vectors of messages are not the same: check first
@@ -22,15 +32,107 @@ fn fast_messages_same(a: &Vector<Message>, b: &Vector<Message>) -> bool {
 	a.front() == b.front() && a.back() == b.back()
 }
 
+/// Row of the message list: the selected channel, whether the chat history is
+/// used to tell new chatters apart, and the message.
+type Row = ((String, bool), Message);
+
 /// The Chat widget.
 #[must_use]
 pub fn widget() -> impl Widget<UIState> {
+	Flex::column()
+		.with_child(channel_select::widget(UIState::chat.then(Chat::channel)))
+		.with_flex_child(messages(|data: &UIState| &data.chat.channel), 1.0)
+}
+
+/// The list of chat messages of the channel selected by the given function.
+/// Chatters new to the channel are flagged if the chat history tells them
+/// apart.
+#[must_use]
+pub fn messages(selection: fn(&UIState) -> &String) -> impl Widget<UIState> {
 	let messages = List::new(|| {
-		Label::dynamic(|message: &Message, _env| format!("{message}"))
-			.with_line_break_mode(LineBreaking::WordWrap)
-			.with_text_alignment(TextAlignment::Start)
-			.padding(Insets::uniform_xy(0.0, 2.0))
+		let row = Label::dynamic(|((selection, history), message): &Row, _env| {
+			let flag = if message.first_message {
+				"[first message] "
+			} else if *history && !message.returning {
+				"[new chatter] "
+			} else {
+				""
+			};
+			let spam = message.spam.map(|kind| format!("[spam: {kind}] ")).unwrap_or_default();
+			if selection.is_empty() {
+				format!("#{} {flag}{spam}{message}", message.channel)
+			} else {
+				format!("{flag}{spam}{message}")
+			}
+		})
+		.with_line_break_mode(LineBreaking::WordWrap)
+		.with_text_alignment(TextAlignment::Start)
+		.padding(Insets::uniform_xy(0.0, 2.0))
+		.env_scope(|env, ((_selection, history), message): &Row| {
+			if message.moderated {
+				env.set(theme::TEXT_COLOR, Color::GRAY);
+			} else if message.spam.is_some() {
+				env.set(theme::TEXT_COLOR, Color::rgb8(0xc0, 0x80, 0x40));
+			} else if message.first_message {
+				env.set(theme::TEXT_COLOR, Color::rgb8(0x91, 0x46, 0xff));
+			} else if *history && !message.returning {
+				env.set(theme::TEXT_COLOR, Color::rgb8(0x40, 0xa0, 0xe0));
+			}
+		});
+		// Messages of other channels are hidden in their rows, so the buffer is
+		// not filtered and copied on every access.
+		Either::new(
+			|((selection, _history), message): &Row, _env| {
+				channel_select::selects(selection, &message.channel)
+			},
+			row,
+			SizedBox::empty(),
+		)
 	});
 
-	Scroll::new(messages).vertical().expand().lens(Chat::messages).lens(UIState::chat)
+	let messages = messages.lens(lens::Map::new(
+		move |data: &UIState| {
+			let shown = (selection(data).clone(), data.settings.uses_history());
+			(shown, data.chat.messages.clone())
+		},
+		|_data: &mut UIState, _messages: ((String, bool), Vector<Message>)| {},
+	));
+
+	Scroll::new(messages)
+		.vertical()
+		.controller(Scrollback::new(
+			move |data: &UIState| older_messages(data, selection(data)),
+			|data: &mut UIState, messages| {
+				data.chat.scrollback += messages.len();
+				data.chat.messages.extend(messages);
+			},
+			|data: &mut UIState| {
+				data.chat.scrollback = 0;
+				data.chat.messages.truncate(data.settings.chat_buffer);
+			},
+		))
+		.expand()
+}
+
+/// Query of the next page of older messages of the selected channel from the
+/// chat history, if it is stored.
+fn older_messages(data: &UIState, selection: &str) -> Option<OlderMessages> {
+	/// Number of messages loaded at once.
+	const PAGE_SIZE: usize = 50;
+
+	if !data.settings.uses_history() {
+		return None;
+	}
+
+	let channels = if selection.is_empty() {
+		data.channels.iter().cloned().collect::<Vec<_>>()
+	} else {
+		vec![selection.to_owned()]
+	};
+	let loaded = data
+		.chat
+		.messages
+		.iter()
+		.filter(|message| channel_select::selects(selection, &message.channel));
+	Some(OlderMessages::new(channels, None, loaded, PAGE_SIZE))
 }
@@ -1,93 +1,69 @@
 //! Giveaway UI.
 
+use std::{error::Error, fmt::Display, str::FromStr};
+
 use druid::{
-	im::{OrdMap, Vector},
-	widget::{Button, Controller, Flex, Label, LineBreaking, List, Scroll, TextBox},
-	Color, Data, Env, EventCtx, Insets, Lens, Widget, WidgetExt,
+	im::Vector,
+	lens,
+	text::ParseFormatter,
+	widget::{
+		Button, Checkbox, CrossAxisAlignment, Flex, Label, LineBreaking, List, Radio, Scroll,
+		TextBox,
+	},
+	Color, Command, Data, Env, EventCtx, Insets, Lens, LensExt, Selector, Target, Widget,
+	WidgetExt,
 };
-use rand::Rng;
 
-use super::settings::SETTINGS_UPDATE;
+use super::{channel_select, scrollback::Scrollback};
 use crate::{
-	chat::{Message, CLEAR_CHAT_MESSAGE, NEW_CHAT_MESSAGE},
+	analytics::Analytics,
+	chat::Message,
+	giveaway::{EligibilityRules, Entrant, Giveaway, MatchMode, Rejection, TicketWeights, Winner},
+	history::OlderMessages,
+	session,
 	ui::UIState,
 };
 
-/// Giveaway UI data/state.
-#[derive(Debug, Clone, Default, Data, Lens)]
-pub struct Giveaway {
-	/// Text to put in front of message to enter the giveaway.
-	message_start: String,
-	/// People who entered the giveaway as a map from ID to display name.
-	#[data(same_fn = "fast_people_entered_same")]
-	people_entered: OrdMap<String, String>,
-	/// Winner
-	winner: Option<String>,
-	/// Winner's messages
-	#[data(same_fn = "fast_winners_messages_same")]
-	winners_messages: Vector<Message>,
-}
-
-/// Simple fast comparison function for entered people. Members are only added,
-/// so compare the length only.
-fn fast_people_entered_same(a: &OrdMap<String, String>, b: &OrdMap<String, String>) -> bool {
-	a.len() == b.len()
-}
-
-/// Fast way to make sure two vectors of messages are not the same: check first
-/// and last element for equality.
-fn fast_winners_messages_same(a: &Vector<Message>, b: &Vector<Message>) -> bool {
-	a.front() == b.front() && a.back() == b.back()
-}
+/// Selector string for rerolling the winner with the ID via a command.
+pub const REROLL_WINNER: Selector<String> = Selector::new("REROLL_WINNER");
 
 /// The UI widget.
 #[must_use]
 pub fn widget() -> impl Widget<UIState> {
-	let chat_column = super::chat::widget().border(Color::GRAY, 1.0);
+	let chat_column = super::chat::messages(|data: &UIState| &data.analytics.giveaway.channel)
+		.border(Color::GRAY, 1.0);
 
-	let message_start = TextBox::new()
-		.with_placeholder("<enter-command>")
-		.expand_width()
-		.lens(Giveaway::message_start);
-	let people_entered =
-		Scroll::new(List::new(|| Label::dynamic(|name: &String, _env| name.clone())))
-			.vertical()
-			.expand()
-			.border(Color::GRAY, 1.0)
-			.lens(Giveaway::people_entered);
+	let ignore_spammers =
+		Checkbox::new("Ignore users flagged as spamming").lens(Giveaway::ignore_spammers);
 	let clear = Button::new("Clear").on_click(on_clear);
-	let draw_winner = Button::new("Draw winner").on_click(on_draw_winner);
+	let winner_count = number_box("Winners:", Giveaway::winner_count);
+	let draw_winners = Button::new("Draw winners").on_click(on_draw_winners);
 	let give_away_column = Flex::column()
-		.with_child(message_start)
+		.with_child(match_mode())
+		.with_child(status())
+		.with_child(ignore_spammers)
+		.with_child(weights())
+		.with_child(rules())
 		.with_default_spacer()
-		.with_flex_child(people_entered, 9.0)
+		.with_flex_child(entries(), 1.0)
 		.with_default_spacer()
 		.with_child(clear)
-		.with_child(draw_winner)
-		.lens(UIState::giveaway);
-
-	let winner = Label::dynamic(|data: &Giveaway, _env| {
-		let winner = data
-			.winner
-			.as_ref()
-			.and_then(|winner| data.people_entered.get(winner))
-			.map(String::as_str)
-			.unwrap_or_default();
-		format!("Winner: {winner}")
-	});
-	let winners_messages = Scroll::new(List::new(|| {
-		Label::dynamic(|msg: &Message, _env| format!("{}: {}", msg.author.name, msg.message))
-			.with_line_break_mode(LineBreaking::WordWrap)
-			.padding(Insets::uniform_xy(0.0, 2.0))
-	}))
-	.vertical()
-	.expand()
-	.border(Color::GRAY, 1.0)
-	.lens(Giveaway::winners_messages);
-	let winner_column = Flex::column()
-		.with_child(winner)
-		.with_flex_child(winners_messages, 9.0)
-		.lens(UIState::giveaway);
+		.with_child(winner_count)
+		.with_child(draw_winners)
+		.lens(UIState::analytics.then(Analytics::giveaway));
+	let give_away_column = Flex::column()
+		.with_child(channel_select::widget(
+			UIState::analytics.then(Analytics::giveaway).then(Giveaway::channel),
+		))
+		.with_flex_child(give_away_column, 1.0);
+
+	let winners = List::new(winner_pane).lens(lens::Map::new(
+		|data: &UIState| (history_channels(data), data.analytics.giveaway.winners.clone()),
+		|data: &mut UIState, (_channels, winners): (Vector<String>, Vector<Winner>)| {
+			data.analytics.giveaway.winners = winners;
+		},
+	));
+	let winner_column = Scroll::new(winners).vertical().expand();
 
 	Flex::row()
 		.with_flex_child(chat_column, 1.0)
@@ -95,65 +71,234 @@ pub fn widget() -> impl Widget<UIState> {
 		.with_flex_child(give_away_column, 1.0)
 		.with_default_spacer()
 		.with_flex_child(winner_column, 1.0)
-		.controller(MessageAnalytics::default())
 }
 
-/// On click of the "clear" button.
-fn on_clear(_ctx: &mut EventCtx, data: &mut Giveaway, _env: &Env) {
-	data.people_entered.clear();
-	data.winner = None;
-	data.winners_messages.clear();
-}
+/// The text to enter the giveaway with, how it is matched and why it is
+/// invalid, if it is.
+fn match_mode() -> impl Widget<Giveaway> {
+	let message_start = TextBox::new()
+		.with_placeholder("<enter-command>")
+		.expand_width()
+		.lens(Giveaway::message_start);
+	let match_mode = Flex::column()
+		.with_child(Radio::new("Command word", MatchMode::Command).expand_width())
+		.with_child(Radio::new("Message start", MatchMode::Prefix).expand_width())
+		.with_child(
+			Radio::new("Message start, ignoring case", MatchMode::CaseInsensitive).expand_width(),
+		)
+		.with_child(Radio::new("Regular expression", MatchMode::Regex).expand_width())
+		.with_child(Radio::new("Any message", MatchMode::Any).expand_width())
+		.lens(Giveaway::match_mode);
+	let validation =
+		Label::dynamic(|data: &Giveaway, _env| data.validation_error().unwrap_or_default())
+			.with_text_color(Color::rgb8(0xe0, 0x40, 0x40))
+			.with_line_break_mode(LineBreaking::WordWrap);
 
-/// On click of the "draw winner" button.
-fn on_draw_winner(_ctx: &mut EventCtx, data: &mut Giveaway, _env: &Env) {
-	let mut rng = rand::thread_rng();
-	let winner = rng.gen_range(0..data.people_entered.len());
-	data.winner = data.people_entered.keys().nth(winner).cloned();
+	Flex::column().with_child(message_start).with_child(match_mode).with_child(validation)
 }
 
-/// Controller for handling chat messages for giveaways.
-#[derive(Debug, Default)]
-struct MessageAnalytics {}
-
-impl<W: Widget<UIState>> Controller<UIState, W> for MessageAnalytics {
-	fn event(
-		&mut self,
-		child: &mut W,
-		ctx: &mut EventCtx,
-		event: &druid::Event,
-		data: &mut UIState,
-		env: &Env,
-	) {
-		if let druid::Event::Command(command) = event {
-			if let Some(message) = command.get(NEW_CHAT_MESSAGE) {
-				// New message, update internal data to show.
-				if message.message.starts_with(&data.giveaway.message_start) {
-					data.giveaway
-						.people_entered
-						.insert(message.author.id.clone(), message.author.name.clone());
-				}
-				if data
-					.giveaway
-					.winner
-					.as_ref()
-					.map_or(false, |winner| *winner == message.author.id)
-				{
-					data.giveaway.winners_messages.push_front(message.as_ref().clone());
-					if data.giveaway.winners_messages.len() > 100 {
-						data.giveaway.winners_messages.truncate(100);
-					}
-				}
-			} else if let Some(_cleared) = command.get(CLEAR_CHAT_MESSAGE) {
-				// Cleared message.
-			} else if command.get(SETTINGS_UPDATE).is_some() {
-				// Settings changed, reset all the data.
-				data.giveaway.people_entered.clear();
-				data.giveaway.winner = None;
-				data.giveaway.winners_messages.clear();
-			}
+/// The duration of the giveaway, and the button to open or close it with the
+/// countdown.
+fn status() -> impl Widget<Giveaway> {
+	let duration = number_box("Duration (s, 0 = unlimited):", Giveaway::duration)
+		.disabled_if(|data: &Giveaway, _env| data.open);
+	let auto_draw =
+		Checkbox::new("Draw winners automatically when time is up").lens(Giveaway::auto_draw);
+	let open = Button::dynamic(|data: &Giveaway, _env| {
+		if data.open { "Close giveaway" } else { "Open giveaway" }.to_owned()
+	})
+	.on_click(on_toggle_open)
+	.disabled_if(|data: &Giveaway, _env| !data.open && data.validation_error().is_some());
+	let status = Label::dynamic(|data: &Giveaway, _env| match (data.open, data.remaining) {
+		(true, Some(remaining)) => {
+			format!("Open for entries, closing in {}:{:02}", remaining / 60, remaining % 60)
 		}
+		(true, None) => "Open for entries".to_owned(),
+		(false, _) => "Closed, no entries accepted".to_owned(),
+	});
+
+	Flex::column()
+		.with_child(duration)
+		.with_child(auto_draw)
+		.with_child(Flex::row().with_child(open).with_default_spacer().with_child(status))
+}
+
+/// The weights of the tickets of the people entered.
+fn weights() -> impl Widget<Giveaway> {
+	Flex::column()
+		.with_child(number_box("Subscriber multiplier:", TicketWeights::sub_multiplier))
+		.with_child(number_box("Multiplier per sub month:", TicketWeights::tenure_multiplier))
+		.with_child(number_box("Bits per ticket (0 = none):", TicketWeights::bits_per_ticket))
+		.with_child(number_box("VIP bonus tickets:", TicketWeights::vip_bonus))
+		.lens(Giveaway::weights)
+}
+
+/// The rules who is eligible to enter.
+fn rules() -> impl Widget<Giveaway> {
+	Flex::column()
+		.with_child(Checkbox::new("Subscribers only").lens(EligibilityRules::subscribers_only))
+		.with_child(Checkbox::new("Exclude moderators").lens(EligibilityRules::exclude_moderators))
+		.with_child(number_box("Min. messages before:", EligibilityRules::min_messages))
+		.with_child(number_box("Min. minutes since first chatted:", EligibilityRules::min_minutes))
+		.with_child(number_box(
+			"Exclude winners of last giveaways:",
+			EligibilityRules::past_giveaways,
+		))
+		.with_child(
+			TextBox::new()
+				.with_placeholder("<excluded-user>, <excluded-user>")
+				.lens(EligibilityRules::excluded_users)
+				.expand_width(),
+		)
+		.cross_axis_alignment(CrossAxisAlignment::Start)
+		.lens(Giveaway::rules)
+}
 
-		child.event(ctx, event, data, env);
+/// The lists of the people entered with their tickets, the ineligible entries
+/// and the late entries.
+fn entries() -> impl Widget<Giveaway> {
+	let people_entered = Scroll::new(List::new(|| {
+		Label::dynamic(|(weights, entrant): &(TicketWeights, Entrant), _env| {
+			format!("{}: {:.1} tickets", entrant.name, weights.tickets(entrant))
+		})
+	}))
+	.vertical()
+	.expand()
+	.border(Color::GRAY, 1.0)
+	.lens(lens::Map::new(
+		|data: &Giveaway| (data.weights.clone(), data.people_entered.clone()),
+		|_data: &mut Giveaway, _entries: (TicketWeights, Vector<Entrant>)| {},
+	));
+	let rejected = Scroll::new(List::new(rejection_row))
+		.vertical()
+		.expand()
+		.border(Color::GRAY, 1.0)
+		.lens(Giveaway::rejected);
+	let late_entries = Scroll::new(List::new(rejection_row))
+		.vertical()
+		.expand()
+		.border(Color::GRAY, 1.0)
+		.lens(Giveaway::late_entries);
+
+	Flex::column()
+		.with_flex_child(people_entered, 6.0)
+		.with_child(Label::new("Ineligible entries"))
+		.with_flex_child(rejected, 3.0)
+		.with_child(Label::new("Late entries"))
+		.with_flex_child(late_entries, 2.0)
+}
+
+/// Text box with a label for a number.
+fn number_box<S: Data, T>(label: &'static str, lens: impl Lens<S, T> + 'static) -> impl Widget<S>
+where
+	T: Data + Display + FromStr,
+	T::Err: Error + 'static,
+{
+	Flex::row().with_child(Label::new(label)).with_flex_child(
+		TextBox::new().with_formatter(ParseFormatter::default()).lens(lens).expand_width(),
+		1.0,
+	)
+}
+
+/// Row of an entry that was not accepted, with the time and the reason.
+fn rejection_row() -> impl Widget<Rejection> {
+	Label::dynamic(|rejection: &Rejection, _env| {
+		format!(
+			"{} {}: {}",
+			session::format_time(rejection.timestamp),
+			rejection.name,
+			rejection.reason
+		)
+	})
+	.with_line_break_mode(LineBreaking::WordWrap)
+}
+
+/// Pane of a winner with their messages, given the channels to page in older
+/// messages from.
+fn winner_pane() -> impl Widget<(Vector<String>, Winner)> {
+	/// Height of the pane's messages.
+	const MESSAGES_HEIGHT: f64 = 200.0;
+
+	let name = Label::dynamic(|(_channels, winner): &(Vector<String>, Winner), _env| {
+		format!("Winner: {}", winner.name)
+	});
+	let reroll = Button::new("Reroll").on_click(
+		|ctx: &mut EventCtx, (_channels, winner): &mut (Vector<String>, Winner), _env: &Env| {
+			ctx.submit_command(Command::new(REROLL_WINNER, winner.id.clone(), Target::Auto));
+		},
+	);
+
+	let messages = List::new(|| {
+		Label::dynamic(|msg: &Message, _env| format!("{}: {}", msg.author.name, msg.message))
+			.with_line_break_mode(LineBreaking::WordWrap)
+			.padding(Insets::uniform_xy(0.0, 2.0))
+	})
+	.lens(lens!((Vector<String>, Winner), 1).then(Winner::messages));
+	let messages = Scroll::new(messages)
+		.vertical()
+		.controller(Scrollback::new(
+			|(channels, winner): &(Vector<String>, Winner)| older_winner_messages(channels, winner),
+			|(_channels, winner): &mut (Vector<String>, Winner), mut messages: Vec<Message>| {
+				// The winner might have been rerolled while the messages were loaded.
+				messages.retain(|message| message.author.id == winner.id);
+				winner.add_older_messages(messages);
+			},
+			|(_channels, winner): &mut (Vector<String>, Winner)| winner.reset_scrollback(),
+		))
+		.fix_height(MESSAGES_HEIGHT)
+		.expand_width()
+		.border(Color::GRAY, 1.0);
+
+	Flex::column()
+		.with_child(Flex::row().with_flex_child(name.expand_width(), 1.0).with_child(reroll))
+		.with_child(messages)
+		.padding(Insets::uniform_xy(0.0, 4.0))
+}
+
+/// The channels to page in older messages of the winners from, none if the
+/// chat history is disabled.
+fn history_channels(data: &UIState) -> Vector<String> {
+	let channel = &data.analytics.giveaway.channel;
+	if !data.settings.uses_history() {
+		Vector::new()
+	} else if channel.is_empty() {
+		data.channels.clone()
+	} else {
+		Vector::unit(channel.clone())
+	}
+}
+
+/// Query of the next page of older messages of the winner from the chat
+/// history of the channels, if there are any.
+fn older_winner_messages(channels: &Vector<String>, winner: &Winner) -> Option<OlderMessages> {
+	/// Number of messages loaded at once.
+	const PAGE_SIZE: usize = 50;
+
+	(!channels.is_empty()).then(|| {
+		OlderMessages::new(
+			channels.iter().cloned().collect(),
+			Some(winner.id.clone()),
+			&winner.messages,
+			PAGE_SIZE,
+		)
+	})
+}
+
+/// On click of the "open/close giveaway" button.
+fn on_toggle_open(_ctx: &mut EventCtx, data: &mut Giveaway, _env: &Env) {
+	if data.open {
+		data.close(session::now());
+	} else {
+		data.open(session::now());
 	}
 }
+
+/// On click of the "clear" button.
+fn on_clear(_ctx: &mut EventCtx, data: &mut Giveaway, _env: &Env) {
+	data.clear();
+}
+
+/// On click of the "draw winners" button.
+fn on_draw_winners(_ctx: &mut EventCtx, data: &mut Giveaway, _env: &Env) {
+	data.draw_winners();
+}
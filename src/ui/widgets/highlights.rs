@@ -0,0 +1,65 @@
+//! Highlights tab.
+
+use std::path::Path;
+
+use druid::{
+	im::Vector,
+	widget::{Button, Flex, Label, LineBreaking, List, Scroll},
+	Color, Env, EventCtx, Insets, LensExt, Widget, WidgetExt,
+};
+
+use crate::{
+	analytics::Analytics,
+	highlights::{self, Highlight},
+	session::format_time,
+	ui::UIState,
+};
+
+/// The file the highlights are exported to.
+const EXPORT_FILE: &str = "highlights.csv";
+
+/// The highlights UI widget.
+#[must_use]
+pub fn widget() -> impl Widget<UIState> {
+	let export = Button::new(format!("Export to {EXPORT_FILE}"))
+		.on_click(on_export)
+		.disabled_if(|data: &Vector<Highlight>, _env| data.is_empty());
+	let count = Label::dynamic(|data: &Vector<Highlight>, _env| {
+		format!("{} highlights in the running session", data.len())
+	});
+
+	let list = Scroll::new(List::new(|| {
+		Label::dynamic(|highlight: &Highlight, _env| {
+			format!(
+				"{} - {} in {} (VOD {})\n{} messages/min (average {:.1}), terms: {}, emotes: {}",
+				format_time(highlight.started),
+				format_time(highlight.ended),
+				highlight.channel,
+				highlight.offset.map(highlights::format_offset).unwrap_or_else(|| "-".to_owned()),
+				highlight.peak_messages,
+				highlight.average,
+				highlight.terms,
+				highlight.emotes,
+			)
+		})
+		.with_line_break_mode(LineBreaking::WordWrap)
+		.padding(Insets::uniform_xy(0.0, 4.0))
+	}))
+	.vertical()
+	.expand()
+	.border(Color::GRAY, 1.0);
+
+	Flex::column()
+		.with_child(Flex::row().with_child(export).with_default_spacer().with_child(count))
+		.with_default_spacer()
+		.with_flex_child(list, 1.0)
+		.lens(UIState::analytics.then(Analytics::highlights))
+}
+
+/// On click of the "export" button.
+fn on_export(_ctx: &mut EventCtx, data: &mut Vector<Highlight>, _env: &Env) {
+	match highlights::export_csv(Path::new(EXPORT_FILE), data.iter()) {
+		Ok(()) => tracing::info!("Exported {} highlights to {EXPORT_FILE}", data.len()),
+		Err(err) => tracing::error!("Exporting highlights failed: {err}"),
+	}
+}
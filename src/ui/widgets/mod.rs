@@ -1,21 +1,39 @@
 //! The UI widgets.
 
+pub mod channel_select;
+pub mod chart;
 pub mod chat;
 pub mod giveaway;
+pub mod highlights;
 pub mod overview;
+pub mod scrollback;
+pub mod sessions;
 pub mod settings;
 
-use std::thread::JoinHandle;
+use std::{
+	thread::JoinHandle,
+	time::{Duration, Instant},
+};
 
 use druid::{
-	widget::{Controller, Tabs, TabsTransition},
-	Widget, WidgetExt,
+	widget::{Controller, Flex, Label, Tabs, TabsTransition},
+	Insets, TimerToken, Widget, WidgetExt,
 };
 use tokio::sync::oneshot;
 
-use self::settings::SETTINGS_UPDATE;
+use self::{
+	giveaway::REROLL_WINNER,
+	sessions::{START_SESSION, STOP_SESSION},
+	settings::SETTINGS_UPDATE,
+};
 use super::UIState;
-use crate::chat::ChatReceiver;
+use crate::{
+	chat::{
+		ChatReceiver, ConnectionStatus, CLEAR_CHAT_MESSAGE, CONNECTION_STATUS, NEW_CHAT_MESSAGE,
+		NEW_USER_NOTICE, USER_BANNED,
+	},
+	history, session, source,
+};
 
 /// The root UI widget.
 #[must_use]
@@ -23,14 +41,28 @@ pub fn root_widget() -> impl Widget<UIState> {
 	let overview = overview::widget();
 	let chat = chat::widget();
 	let giveaway = giveaway::widget();
+	let highlights = highlights::widget();
+	let sessions = sessions::widget();
 	let settings = settings::widget();
 
-	Tabs::new()
+	let tabs = Tabs::new()
 		.with_transition(TabsTransition::Slide(100_000_000))
 		.with_tab("Overview", overview)
 		.with_tab("Chat", chat)
 		.with_tab("Giveaway", giveaway)
-		.with_tab("Settings", settings)
+		.with_tab("Highlights", highlights)
+		.with_tab("Sessions", sessions)
+		.with_tab("Settings", settings);
+
+	let status_bar = Label::dynamic(|status: &ConnectionStatus, _env| format!("Status: {status}"))
+		.padding(Insets::uniform_xy(5.0, 2.0))
+		.expand_width()
+		.lens(UIState::connection);
+
+	Flex::column()
+		.with_flex_child(tabs, 1.0)
+		.with_child(status_bar)
+		.controller(AnalyticsUpdater::default())
 		.controller(ChatReceiverSpawner::default())
 }
 
@@ -44,6 +76,21 @@ struct ChatReceiverSpawner {
 	stop_trigger: Option<oneshot::Sender<()>>,
 }
 
+impl Drop for ChatReceiverSpawner {
+	fn drop(&mut self) {
+		// Stop the chat listener and wait until the remaining events are written
+		// to the chat history, before the app exits.
+		if let Some(stop_trigger) = self.stop_trigger.take() {
+			stop_trigger.send(()).ok();
+		}
+		if let Some(join_handle) = self.join_handle.take() {
+			if join_handle.join().is_err() {
+				tracing::error!("Chat receiver thread panicked");
+			}
+		}
+	}
+}
+
 impl<W: Widget<UIState>> Controller<UIState, W> for ChatReceiverSpawner {
 	fn event(
 		&mut self,
@@ -54,25 +101,126 @@ impl<W: Widget<UIState>> Controller<UIState, W> for ChatReceiverSpawner {
 		env: &druid::Env,
 	) {
 		if let druid::Event::Command(command) = event {
-			if command.get(SETTINGS_UPDATE).is_some() {
+			if let Some(status) = command.get(CONNECTION_STATUS) {
+				data.connection = status.clone();
+			} else if command.get(SETTINGS_UPDATE).is_some() {
 				// Clean up previous client thread
 				if let Some(stop_trigger) = self.stop_trigger.take() {
 					stop_trigger.send(()).ok();
 				}
 				data.chat.messages.clear();
+				data.chat.scrollback = 0;
+				data.connection = ConnectionStatus::Disconnected;
+
+				// Reset channel selections that are not available anymore
+				let channels = data.settings.channels();
+				for selection in [
+					&mut data.overview.channel,
+					&mut data.chat.channel,
+					&mut data.analytics.giveaway.channel,
+				] {
+					if !channels.contains(selection) {
+						selection.clear();
+					}
+				}
+				data.channels = channels.into_iter().collect();
+
+				self.spawn_receiver(ctx, data);
+			}
+		}
+
+		child.event(ctx, event, data, env);
+	}
+}
+
+impl ChatReceiverSpawner {
+	/// Start a new client for the channels, or replay a recording instead.
+	/// Without channels nothing is started and the status stays disconnected.
+	fn spawn_receiver(&mut self, ctx: &mut druid::EventCtx, data: &UIState) {
+		if data.channels.is_empty() && data.settings.replay().is_none() {
+			return;
+		}
 
-				// Start new client for new channel
-				let (stop_trigger_sender, stop_trigger_receiver) = oneshot::channel();
-				let join_handle = ChatReceiver::builder()
-					.channel(data.settings.twitch_channel.to_lowercase())
-					.event_sender(ctx.get_external_handle())
-					.stop_trigger(stop_trigger_receiver)
-					.build()
-					.spawn();
-
-				self.join_handle = Some(join_handle);
-				self.stop_trigger = Some(stop_trigger_sender);
+		let (history, known_chatters) = history::from_settings(&data.settings);
+		let source = source::from_settings(&data.settings);
+
+		let (stop_trigger_sender, stop_trigger_receiver) = oneshot::channel();
+		let join_handle = ChatReceiver::builder()
+			.source(source)
+			.sink(Box::new(ctx.get_external_handle()))
+			.history(history)
+			.known_chatters(known_chatters)
+			.stop_trigger(stop_trigger_receiver)
+			.build()
+			.spawn();
+
+		self.join_handle = Some(join_handle);
+		self.stop_trigger = Some(stop_trigger_sender);
+	}
+}
+
+/// Controller feeding the chat events and user actions into the analytics
+/// engine, which the widgets observe.
+#[derive(Debug, Default)]
+struct AnalyticsUpdater {
+	/// Timer for checking chat inactivity.
+	timer: Option<TimerToken>,
+	/// Timer for counting down the giveaway and updating the trending terms.
+	countdown: Option<TimerToken>,
+}
+
+impl AnalyticsUpdater {
+	/// Interval to check for chat inactivity.
+	const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+	/// Interval to update the giveaway countdown and the trending terms.
+	const COUNTDOWN_INTERVAL: Duration = Duration::from_secs(1);
+}
+
+impl<W: Widget<UIState>> Controller<UIState, W> for AnalyticsUpdater {
+	fn event(
+		&mut self,
+		child: &mut W,
+		ctx: &mut druid::EventCtx,
+		event: &druid::Event,
+		data: &mut UIState,
+		env: &druid::Env,
+	) {
+		match event {
+			druid::Event::WindowConnected => {
+				data.analytics.sessions.load().ok();
+				data.analytics.giveaway.load_past_winners().ok();
+				self.timer = Some(ctx.request_timer(Self::CHECK_INTERVAL));
+				self.countdown = Some(ctx.request_timer(Self::COUNTDOWN_INTERVAL));
+			}
+			druid::Event::Timer(token) if Some(*token) == self.timer => {
+				data.analytics.check_inactivity(Instant::now());
+				self.timer = Some(ctx.request_timer(Self::CHECK_INTERVAL));
+			}
+			druid::Event::Timer(token) if Some(*token) == self.countdown => {
+				data.analytics.giveaway.tick(session::now());
+				data.analytics.metrics.update_trending_terms();
+				self.countdown = Some(ctx.request_timer(Self::COUNTDOWN_INTERVAL));
+			}
+			druid::Event::Command(command) => {
+				if let Some(message) = command.get(NEW_CHAT_MESSAGE) {
+					data.analytics.handle_message(message);
+				} else if let Some(cleared) = command.get(CLEAR_CHAT_MESSAGE) {
+					data.analytics.handle_clear_message(cleared);
+				} else if let Some(notice) = command.get(NEW_USER_NOTICE) {
+					data.analytics.handle_user_notice(notice);
+				} else if let Some(ban) = command.get(USER_BANNED) {
+					data.analytics.handle_ban(ban);
+				} else if command.get(START_SESSION).is_some() {
+					data.analytics.start_session(session::now());
+				} else if command.get(STOP_SESSION).is_some() {
+					data.analytics.stop_session_manually(session::now());
+				} else if let Some(winner_id) = command.get(REROLL_WINNER) {
+					data.analytics.giveaway.reroll(winner_id);
+				} else if command.get(SETTINGS_UPDATE).is_some() {
+					data.analytics.apply_settings(&data.settings);
+				}
 			}
+			_ => {}
 		}
 
 		child.event(ctx, event, data, env);
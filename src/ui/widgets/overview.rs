@@ -1,113 +1,340 @@
 //! Overview tab.
 
 use druid::{
-	im::HashMap,
-	widget::{Controller, Flex, Label},
-	Data, Lens, Widget, WidgetExt,
+	im::{HashMap, Vector},
+	lens,
+	widget::{Button, CrossAxisAlignment, Flex, Label, List, Radio, Scroll},
+	Color, Data, Lens, LensExt, Widget, WidgetExt,
 };
 
-use super::settings::SETTINGS_UPDATE;
+use super::{channel_select, chart::LineChart};
 use crate::{
-	chat::{CLEAR_CHAT_MESSAGE, NEW_CHAT_MESSAGE},
+	metrics::{ChatterOrder, ChatterStats, EmoteOrder, EmoteStats, Metrics},
+	session,
+	spam::SpamCounts,
+	terms::{TermFrequency, TrendingTerm},
+	timeseries::{Bucket, SeriesWindow, TimeSeries},
 	ui::UIState,
 };
 
 /// Overview UI data/state.
 #[derive(Debug, Clone, Default, Data, Lens)]
 pub struct Overview {
-	/// Map from user IDs to user names.
-	#[data(same_fn = "fast_unique_chatters_same")]
-	unique_chatters: HashMap<String, String>,
-	/// Number of total messages.
-	total_messages: usize,
-	/// Number of messages by subscribers.
-	subscriber_messages: usize,
-	/// Number of total bits cheered.
-	total_bits: u64,
-	/// Number of messages cleared.
-	messages_cleared: usize,
-}
-
-/// Simple fast comparison function for unique chatters. Members are only added,
-/// so compare the length only.
-fn fast_unique_chatters_same(a: &HashMap<String, String>, b: &HashMap<String, String>) -> bool {
-	a.len() == b.len()
+	/// Selected channel to show the metrics of, empty for all channels.
+	pub channel: String,
+	/// Selected window of the charts.
+	pub window: SeriesWindow,
+	/// Selected order of the top chatters.
+	pub chatter_order: ChatterOrder,
+	/// Selected order of the top emotes.
+	pub emote_order: EmoteOrder,
 }
 
 /// The overview UI widget.
 #[must_use]
 pub fn widget() -> impl Widget<UIState> {
-	let unique_chatters = Label::dynamic(|chatters: &HashMap<String, String>, _env| {
+	let session =
+		Label::dynamic(|data: &UIState, _env| match data.analytics.sessions.running_since {
+			Some(started) => format!("Session running since {}", session::format_time(started)),
+			None => "No session running".to_owned(),
+		});
+
+	Scroll::new(
+		Flex::column()
+			.with_child(channel_select::widget(UIState::overview.then(Overview::channel)))
+			.with_child(session)
+			.with_default_spacer()
+			.with_child(
+				Flex::row()
+					.cross_axis_alignment(CrossAxisAlignment::Start)
+					.with_flex_child(
+						Flex::column()
+							.cross_axis_alignment(CrossAxisAlignment::Start)
+							.with_child(metrics())
+							.with_default_spacer()
+							.with_child(top_emotes()),
+						1.0,
+					)
+					.with_default_spacer()
+					.with_flex_child(
+						Flex::column()
+							.cross_axis_alignment(CrossAxisAlignment::Start)
+							.with_child(leaderboard())
+							.with_default_spacer()
+							.with_child(trending_terms()),
+						1.0,
+					),
+			)
+			.with_default_spacer()
+			.with_child(charts()),
+	)
+	.vertical()
+	.expand()
+}
+
+/// The labels of the metrics of the selected channel.
+fn metrics() -> impl Widget<UIState> {
+	let unique_chatters = Label::dynamic(|chatters: &HashMap<String, ChatterStats>, _env| {
 		format!("Unique chatters: {}", chatters.len())
 	})
-	.lens(Overview::unique_chatters);
+	.lens(Metrics::chatters);
+
+	let new_chatters = Label::dynamic(|data: &Metrics, _env| {
+		format!(
+			"New chatters: {} ({} first-time), returning: {} ({:.2}% retention)",
+			data.new_chatters,
+			data.first_time_chatters,
+			data.returning_chatters,
+			100.0 * data.returning_chatters as f64 / data.chatters.len().max(1) as f64
+		)
+	});
 
 	let total_messages = Label::dynamic(|num: &usize, _env| format!("Total messages: {num}"))
-		.lens(Overview::total_messages);
+		.lens(Metrics::total_messages);
 
-	let subscriber_messages = Label::dynamic(|data: &Overview, _env| {
+	let role_messages = Label::dynamic(|data: &Metrics, _env| {
+		let share = |messages: usize| 100.0 * messages as f64 / data.total_messages.max(1) as f64;
 		format!(
-			"Fraction of messages by subscribers: {:.2}%",
-			100.0 * data.subscriber_messages as f64 / data.total_messages as f64
+			"Share of messages by subscribers: {:.2}%, VIPs: {:.2}%, moderators: {:.2}%, \
+			 non-subscribers: {:.2}%",
+			share(data.subscriber_messages),
+			share(data.vip_messages),
+			share(data.moderator_messages),
+			share(data.total_messages - data.subscriber_messages)
 		)
 	});
 
 	let total_bits =
-		Label::dynamic(|bits: &u64, _env| format!("Total bits: {bits}")).lens(Overview::total_bits);
+		Label::dynamic(|bits: &u64, _env| format!("Total bits: {bits}")).lens(Metrics::total_bits);
 
 	let messages_cleared =
 		Label::dynamic(|cleared: &usize, _env| format!("Messages deleted: {cleared}"))
-			.lens(Overview::messages_cleared);
+			.lens(Metrics::messages_cleared);
+
+	let spam = Label::dynamic(|spam: &SpamCounts, _env| {
+		format!(
+			"Spam messages: {} ({} repeated, {} copypasta, {} caps, {} emote-only)",
+			spam.total(),
+			spam.repeated,
+			spam.copypasta,
+			spam.caps,
+			spam.emote_only
+		)
+	})
+	.lens(Metrics::spam);
+
+	let subs = Label::dynamic(|data: &Metrics, _env| {
+		format!(
+			"Subscriptions: {} new, {} resubs, {} gifted",
+			data.new_subs, data.resubs, data.gifted_subs
+		)
+	});
+
+	let raids = Label::dynamic(|data: &Metrics, _env| {
+		format!("Raids: {} with {} viewers", data.raids, data.raid_viewers)
+	});
+
+	let announcements = Label::dynamic(|num: &usize, _env| format!("Announcements: {num}"))
+		.lens(Metrics::announcements);
+
+	let bans = Label::dynamic(|data: &Metrics, _env| {
+		format!("Users banned: {}, timed out: {}", data.bans, data.timeouts)
+	});
 
 	Flex::column()
 		.with_child(unique_chatters)
+		.with_child(new_chatters)
 		.with_child(total_messages)
-		.with_child(subscriber_messages)
+		.with_child(role_messages)
 		.with_child(total_bits)
 		.with_child(messages_cleared)
-		.lens(UIState::overview)
-		.controller(MessageAnalytics::default())
-		.expand()
+		.with_child(spam)
+		.with_child(subs)
+		.with_child(raids)
+		.with_child(announcements)
+		.with_child(bans)
+		.lens(lens::Map::new(
+			|data: &UIState| data.analytics.metrics.channel(&data.overview.channel),
+			|_data: &mut UIState, _metrics: Metrics| {},
+		))
+}
+
+/// Table of the top chatters in the selected channel, sortable by messages or
+/// bits.
+fn leaderboard() -> impl Widget<UIState> {
+	let header = Flex::row()
+		.with_flex_child(Label::new("Chatter").expand_width(), 2.0)
+		.with_flex_child(
+			Button::new("Messages")
+				.on_click(|_ctx, order: &mut ChatterOrder, _env| *order = ChatterOrder::Messages)
+				.disabled_if(|order, _env| *order == ChatterOrder::Messages)
+				.expand_width(),
+			1.0,
+		)
+		.with_flex_child(
+			Button::new("Bits")
+				.on_click(|_ctx, order: &mut ChatterOrder, _env| *order = ChatterOrder::Bits)
+				.disabled_if(|order, _env| *order == ChatterOrder::Bits)
+				.expand_width(),
+			1.0,
+		)
+		.with_flex_child(Label::new("First seen").expand_width(), 2.0)
+		.with_flex_child(Label::new("Last seen").expand_width(), 2.0)
+		.lens(UIState::overview.then(Overview::chatter_order));
+
+	let rows = List::new(|| {
+		Flex::row()
+			.with_flex_child(
+				Label::dynamic(|chatter: &ChatterStats, _env| chatter.name.clone()).expand_width(),
+				2.0,
+			)
+			.with_flex_child(
+				Label::dynamic(|chatter: &ChatterStats, _env| chatter.messages.to_string())
+					.expand_width(),
+				1.0,
+			)
+			.with_flex_child(
+				Label::dynamic(|chatter: &ChatterStats, _env| chatter.bits.to_string())
+					.expand_width(),
+				1.0,
+			)
+			.with_flex_child(
+				Label::dynamic(|chatter: &ChatterStats, _env| {
+					session::format_time(chatter.first_seen)
+				})
+				.expand_width(),
+				2.0,
+			)
+			.with_flex_child(
+				Label::dynamic(|chatter: &ChatterStats, _env| {
+					session::format_time(chatter.last_seen)
+				})
+				.expand_width(),
+				2.0,
+			)
+	})
+	.lens(lens::Map::new(
+		|data: &UIState| {
+			data.analytics
+				.metrics
+				.channel(&data.overview.channel)
+				.top_chatters(data.overview.chatter_order)
+				.clone()
+		},
+		|_data: &mut UIState, _chatters: Vector<ChatterStats>| {},
+	));
+
+	Flex::column().with_child(Label::new("Top chatters")).with_child(header).with_child(rows)
+}
+
+/// List of the most or least used emotes in the selected channel.
+fn top_emotes() -> impl Widget<UIState> {
+	let header = Flex::row()
+		.with_child(Label::new("Emotes"))
+		.with_default_spacer()
+		.with_child(
+			Button::new("Most used")
+				.on_click(|_ctx, order: &mut EmoteOrder, _env| *order = EmoteOrder::MostUsed)
+				.disabled_if(|order, _env| *order == EmoteOrder::MostUsed),
+		)
+		.with_child(
+			Button::new("Least used")
+				.on_click(|_ctx, order: &mut EmoteOrder, _env| *order = EmoteOrder::LeastUsed)
+				.disabled_if(|order, _env| *order == EmoteOrder::LeastUsed),
+		)
+		.lens(UIState::overview.then(Overview::emote_order));
+
+	let emotes = List::new(|| {
+		Label::dynamic(|emote: &EmoteStats, _env| {
+			format!("{}: {} times by {} chatters", emote.code, emote.uses, emote.unique_users)
+		})
+	})
+	.lens(lens::Map::new(
+		|data: &UIState| {
+			data.analytics
+				.metrics
+				.channel(&data.overview.channel)
+				.top_emotes(data.overview.emote_order)
+				.clone()
+		},
+		|_data: &mut UIState, _emotes: Vector<EmoteStats>| {},
+	));
+
+	Flex::column()
+		.cross_axis_alignment(CrossAxisAlignment::Start)
+		.with_child(header)
+		.with_child(emotes)
+}
+
+/// List of the terms trending in the last minutes in the selected channel.
+fn trending_terms() -> impl Widget<UIState> {
+	let terms = List::new(|| {
+		Label::dynamic(|term: &TrendingTerm, _env| {
+			format!("{}: {} messages ({:.1}x)", term.term, term.recent, term.factor)
+		})
+	})
+	.lens(lens::Map::new(
+		|data: &UIState| {
+			data.analytics.metrics.channel(&data.overview.channel).trending_terms().clone()
+		},
+		|_data: &mut UIState, _terms: Vector<TrendingTerm>| {},
+	));
+
+	Flex::column()
+		.cross_axis_alignment(CrossAxisAlignment::Start)
+		.with_child(Label::new(format!(
+			"Trending terms (last {} min)",
+			TermFrequency::RECENT_MINUTES
+		)))
+		.with_child(terms)
 }
 
-/// Controller for calculating chat analytics.
-#[derive(Debug, Default)]
-struct MessageAnalytics {}
-
-impl<W: Widget<UIState>> Controller<UIState, W> for MessageAnalytics {
-	fn event(
-		&mut self,
-		child: &mut W,
-		ctx: &mut druid::EventCtx,
-		event: &druid::Event,
-		data: &mut UIState,
-		env: &druid::Env,
-	) {
-		if let druid::Event::Command(command) = event {
-			if let Some(message) = command.get(NEW_CHAT_MESSAGE) {
-				// New message, update internal data to show.
-				data.overview
-					.unique_chatters
-					.entry(message.author.id.clone())
-					.or_insert(message.author.name.clone());
-				data.overview.total_messages += 1;
-				data.overview.total_bits += message.bits.unwrap_or_default();
-
-				if message.subscriber {
-					data.overview.subscriber_messages += 1;
-				}
-			} else if let Some(_cleared) = command.get(CLEAR_CHAT_MESSAGE) {
-				data.overview.messages_cleared += 1;
-			} else if command.get(SETTINGS_UPDATE).is_some() {
-				// Settings changed, reset all the data.
-				data.overview.unique_chatters.clear();
-				data.overview.total_messages = 0;
-				data.overview.subscriber_messages = 0;
-				data.overview.total_bits = 0;
-				data.overview.messages_cleared = 0;
-			}
-		}
-
-		child.event(ctx, event, data, env);
-	}
+/// The charts of the activity per minute with the window selection.
+fn charts() -> impl Widget<UIState> {
+	let window = Flex::row()
+		.with_child(Radio::new("Last 10 min", SeriesWindow::TenMinutes))
+		.with_child(Radio::new("Last hour", SeriesWindow::OneHour))
+		.with_child(Radio::new("Whole session", SeriesWindow::Session))
+		.lens(UIState::overview.then(Overview::window));
+
+	let left = Flex::column()
+		.with_child(chart("Messages per minute", Color::rgb8(0x91, 0x46, 0xff), |bucket| {
+			bucket.messages as f64
+		}))
+		.with_default_spacer()
+		.with_child(chart("Bits per minute", Color::rgb8(0xff, 0xa5, 0x00), |bucket| {
+			bucket.bits as f64
+		}))
+		.with_default_spacer()
+		.with_child(chart("Emotes per minute", Color::rgb8(0xe0, 0x40, 0x80), |bucket| {
+			bucket.emotes as f64
+		}));
+	let right = Flex::column()
+		.with_child(chart("Chatters per minute", Color::rgb8(0x00, 0xa0, 0xe0), |bucket| {
+			bucket.unique_chatters as f64
+		}))
+		.with_default_spacer()
+		.with_child(chart("Subscriptions per minute", Color::rgb8(0x00, 0xc0, 0x60), |bucket| {
+			bucket.subs as f64
+		}));
+
+	Flex::column().with_child(window).with_default_spacer().with_child(
+		Flex::row().with_flex_child(left, 1.0).with_default_spacer().with_flex_child(right, 1.0),
+	)
+}
+
+/// A chart of one value of the activity per minute in the selected channel and
+/// window.
+fn chart(title: &'static str, color: Color, value: fn(&Bucket) -> f64) -> impl Widget<UIState> {
+	let label = Label::dynamic(move |(series, window): &(TimeSeries, SeriesWindow), _env| {
+		format!("{title} (max {})", series.max(*window, value))
+	});
+
+	Flex::column().with_child(label).with_child(LineChart::new(color, value)).lens(lens::Map::new(
+		|data: &UIState| {
+			let series = &data.analytics.metrics.channel(&data.overview.channel).series;
+			(series.clone(), data.overview.window)
+		},
+		|_data: &mut UIState, _series: (TimeSeries, SeriesWindow)| {},
+	))
 }
@@ -0,0 +1,158 @@
+//! Scrollback of message lists, paging in older messages from the chat history.
+
+use std::{fmt::Debug, path::Path, sync::Arc, thread};
+
+use druid::{
+	widget::{Controller, Scroll},
+	Data, Env, Event, EventCtx, Selector, Target, Widget,
+};
+
+use crate::{
+	chat::Message,
+	history::{self, OlderMessages},
+};
+
+/// Selector string for the commands delivering a loaded page of older
+/// messages to the scrollback that requested it.
+const OLDER_MESSAGES_LOADED: Selector<Arc<LoadedPage>> = Selector::new("OLDER_MESSAGES_LOADED");
+
+/// A page of older messages loaded on a worker thread.
+#[derive(Debug)]
+struct LoadedPage {
+	/// The scrollback's generation when the page was requested.
+	generation: usize,
+	/// The loaded messages, newest first.
+	messages: Vec<Message>,
+}
+
+/// Controller of a scrolled list of messages, newest first. It loads older
+/// messages on a worker thread when scrolling on at the end of the list, be it
+/// with the wheel, the scroll bar or the keyboard, and drops them again when
+/// scrolled back to the start.
+pub struct Scrollback<T> {
+	/// Query of a page of older messages, if there is a history to load from.
+	load_older: Box<dyn Fn(&T) -> Option<OlderMessages>>,
+	/// Add a loaded page of older messages.
+	add_older: Box<dyn Fn(&mut T, Vec<Message>)>,
+	/// Drop the older messages that were loaded.
+	reset: Box<dyn Fn(&mut T)>,
+	/// Height of the list when older messages were last loaded, to not try
+	/// again before the list changed.
+	loaded_height: Option<f64>,
+	/// Whether the list was scrolled to the start.
+	at_start: bool,
+	/// Scroll offset after the last event, to notice scrolling towards the
+	/// end by any means, e.g. dragging the scroll bar.
+	offset: f64,
+	/// Whether a page of older messages is being loaded.
+	loading: bool,
+	/// Counter of the resets, to drop pages requested before the last one.
+	generation: usize,
+}
+
+impl<T> Scrollback<T> {
+	/// Distance to the end of the list in pixels, within which older messages
+	/// are loaded.
+	const MARGIN: f64 = 20.0;
+
+	/// Create the controller with the functions to query older messages, to add
+	/// them once loaded and to drop them again.
+	#[must_use]
+	pub fn new(
+		load_older: impl Fn(&T) -> Option<OlderMessages> + 'static,
+		add_older: impl Fn(&mut T, Vec<Message>) + 'static,
+		reset: impl Fn(&mut T) + 'static,
+	) -> Self {
+		Self {
+			load_older: Box::new(load_older),
+			add_older: Box::new(add_older),
+			reset: Box::new(reset),
+			loaded_height: None,
+			at_start: true,
+			offset: 0.0,
+			loading: false,
+			generation: 0,
+		}
+	}
+
+	/// Load the older messages on a worker thread, which delivers them back to
+	/// this widget, so that the UI does not wait for the database.
+	fn spawn_loading(&mut self, ctx: &EventCtx, query: OlderMessages) {
+		let sink = ctx.get_external_handle();
+		let target = Target::Widget(ctx.widget_id());
+		let generation = self.generation;
+		self.loading = true;
+		thread::spawn(move || {
+			let messages = query.load(Path::new(history::DIRECTORY)).unwrap_or_else(|err| {
+				tracing::error!("Loading older messages failed: {err}");
+				Vec::new()
+			});
+			let page = LoadedPage { generation, messages };
+			// Fails only if the app is closing already.
+			sink.submit_command(OLDER_MESSAGES_LOADED, Arc::new(page), target).ok();
+		});
+	}
+}
+
+impl<T> Debug for Scrollback<T> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Scrollback")
+			.field("loaded_height", &self.loaded_height)
+			.field("at_start", &self.at_start)
+			.field("offset", &self.offset)
+			.field("loading", &self.loading)
+			.field("generation", &self.generation)
+			.finish_non_exhaustive()
+	}
+}
+
+impl<T: Data, W: Widget<T>> Controller<T, Scroll<T, W>> for Scrollback<T> {
+	fn event(
+		&mut self,
+		child: &mut Scroll<T, W>,
+		ctx: &mut EventCtx,
+		event: &Event,
+		data: &mut T,
+		env: &Env,
+	) {
+		if let Event::Command(command) = event {
+			if let Some(page) = command.get(OLDER_MESSAGES_LOADED) {
+				// Pages requested before scrolling back to the start are outdated.
+				if page.generation == self.generation {
+					self.loading = false;
+					(self.add_older)(data, page.messages.clone());
+				}
+				ctx.set_handled();
+				return;
+			}
+		}
+
+		child.event(ctx, event, data, env);
+
+		let viewport = child.viewport_rect();
+		let height = child.child_size().height;
+		// Wheeling on at the end does not move the viewport anymore.
+		let scrolled_on = viewport.y0 > self.offset
+			|| matches!(event, Event::Wheel(mouse) if mouse.wheel_delta.y > 0.0);
+		self.offset = viewport.y0;
+		if scrolled_on
+			&& viewport.y1 >= height - Self::MARGIN
+			&& self.loaded_height != Some(height)
+			&& !self.loading
+		{
+			if let Some(query) = (self.load_older)(data) {
+				self.loaded_height = Some(height);
+				self.spawn_loading(ctx, query);
+			}
+		}
+
+		let at_start = viewport.y0 <= 0.0;
+		if at_start && !self.at_start {
+			self.loaded_height = None;
+			self.loading = false;
+			self.generation += 1;
+			(self.reset)(data);
+		}
+		self.at_start = at_start;
+	}
+}
@@ -0,0 +1,81 @@
+//! Sessions tab.
+
+use druid::{
+	widget::{Button, Flex, Label, LineBreaking, List, Scroll},
+	Color, Command, Env, EventCtx, Insets, LensExt, Selector, Target, Widget, WidgetExt,
+};
+
+use crate::{
+	analytics::Analytics,
+	session::{format_time, Session, Sessions},
+	ui::UIState,
+};
+
+/// Selector string for starting a new session via a command.
+pub const START_SESSION: Selector<()> = Selector::new("START_SESSION");
+/// Selector string for stopping the running session via a command.
+pub const STOP_SESSION: Selector<()> = Selector::new("STOP_SESSION");
+
+/// The sessions UI widget.
+#[must_use]
+pub fn widget() -> impl Widget<UIState> {
+	let start = Button::new("Start session")
+		.on_click(|ctx: &mut EventCtx, _data: &mut Sessions, _env: &Env| {
+			ctx.submit_command(Command::new(START_SESSION, (), Target::Auto));
+		})
+		.disabled_if(|data: &Sessions, _env| data.running_since.is_some());
+	let stop = Button::new("Stop session")
+		.on_click(|ctx: &mut EventCtx, _data: &mut Sessions, _env: &Env| {
+			ctx.submit_command(Command::new(STOP_SESSION, (), Target::Auto));
+		})
+		.disabled_if(|data: &Sessions, _env| data.running_since.is_none());
+	let running = Label::dynamic(|running_since: &Option<i64>, _env| match running_since {
+		Some(started) => format!("Session running since {}", format_time(*started)),
+		None => "No session running".to_owned(),
+	})
+	.lens(Sessions::running_since);
+
+	let past = Scroll::new(List::new(|| {
+		Label::dynamic(|session: &Session, _env| {
+			let totals = &session.totals;
+			let minutes = (session.ended - session.started) / 60;
+			format!(
+				"{} - {} ({}h {}min) in {}\n{} messages by {} chatters ({} new, {} returning), {} \
+				 bits, {} new subs, {} resubs, {} gifted subs, {} raids with {} viewers, {} \
+				 announcements, {} messages deleted, {} bans, {} timeouts",
+				format_time(session.started),
+				format_time(session.ended),
+				minutes / 60,
+				minutes % 60,
+				session.channels,
+				totals.total_messages,
+				totals.unique_chatters,
+				totals.new_chatters,
+				totals.returning_chatters,
+				totals.total_bits,
+				totals.new_subs,
+				totals.resubs,
+				totals.gifted_subs,
+				totals.raids,
+				totals.raid_viewers,
+				totals.announcements,
+				totals.messages_cleared,
+				totals.bans,
+				totals.timeouts,
+			)
+		})
+		.with_line_break_mode(LineBreaking::WordWrap)
+		.padding(Insets::uniform_xy(0.0, 4.0))
+	}))
+	.vertical()
+	.expand()
+	.border(Color::GRAY, 1.0)
+	.lens(Sessions::past);
+
+	Flex::column()
+		.with_child(Flex::row().with_child(start).with_default_spacer().with_child(stop))
+		.with_child(running)
+		.with_default_spacer()
+		.with_flex_child(past, 1.0)
+		.lens(UIState::analytics.then(Analytics::sessions))
+}
@@ -1,81 +1,44 @@
 //! Settings tab.
 
-use std::fs::File;
-
-use color_eyre::Result;
 use druid::{
 	text::ParseFormatter,
-	widget::{Button, Controller, Flex, Label, TextBox},
-	Command, Data, Env, EventCtx, Lens, Selector, Target, Widget, WidgetExt,
+	widget::{Button, Checkbox, Controller, Flex, Label, TextBox},
+	Application, Color, Command, Data, Env, EventCtx, HotKey, KbKey, LifeCycle, LifeCycleCtx,
+	Selector, SysMods, Target, Widget, WidgetExt,
 };
-use serde::{Deserialize, Serialize};
 
-use crate::ui::UIState;
+use crate::{settings::Settings, ui::UIState};
 
 /// Selector string for notifying of settings updates via a command.
 pub const SETTINGS_UPDATE: Selector<()> = Selector::new("SETTINGS_UPDATE");
 
-/// The Settings data + UI state.
-#[derive(Debug, Clone, Serialize, Deserialize, Data, Lens)]
-pub struct Settings {
-	/// Indicates whether a setting was edited and need to be saved.
-	#[serde(default, skip_serializing)]
-	pub changes: bool,
-	#[serde(default)]
-	/// The twitch channel login name.
-	pub twitch_channel: String,
-	/// The number of messages the chat should buffer.
-	#[serde(default = "Settings::default_chat_buffer")]
-	pub chat_buffer: usize,
-}
-
-impl Default for Settings {
-	fn default() -> Self {
-		Self {
-			changes: false,
-			twitch_channel: String::new(),
-			chat_buffer: Self::default_chat_buffer(),
-		}
-	}
-}
-
-impl Settings {
-	/// The filename where the settings file is stored.
-	const STATE_FILE: &'static str = "settings.json";
-
-	/// Load the settings from the file.
-	pub fn from_file() -> Result<Self> {
-		let file = File::open(Self::STATE_FILE)?;
-		let state = serde_json::from_reader(file)?;
-		Ok(state)
-	}
-
-	/// Save the settings to the file.
-	pub fn save(&self) -> Result<()> {
-		let file = File::create(Self::STATE_FILE)?;
-		serde_json::to_writer_pretty(file, self)?;
-		Ok(())
-	}
-
-	/// Settings default value for `chat_buffer`.
-	fn default_chat_buffer() -> usize {
-		250
-	}
-}
-
 /// The settings UI widget.
 #[must_use]
 pub fn widget() -> impl Widget<UIState> {
 	let labels = Flex::column()
-		.with_child(Label::new("Twitch channel: "))
-		.with_child(Label::new("Chat buffer size:"));
+		.with_child(Label::new("Twitch channels: "))
+		.with_child(Label::new("Chat buffer size:"))
+		.with_child(Label::new("Session timeout (min):"))
+		.with_child(Label::new("Chat history:"))
+		.with_child(Label::new("Chat recording:"))
+		.with_child(Label::new("Replay file:"))
+		.with_child(Label::new("Replay speed:"))
+		.with_child(Label::new("Stop words:"))
+		.with_child(Label::new("Min. term length:"))
+		.with_child(Label::new("Highlight factor:"))
+		.with_child(Label::new("IRC server:"))
+		.with_child(Label::new("Bot login name:"))
+		.with_child(Label::new("OAuth token:"))
+		.with_child(Label::new("Refresh token:"))
+		.with_child(Label::new("Client ID:"))
+		.with_child(Label::new("Client secret:"));
 
 	let text_boxes = Flex::column()
 		.with_child(
 			TextBox::new()
 				.with_line_wrapping(true)
-				.with_placeholder("<channel-name>")
-				.lens(Settings::twitch_channel)
+				.with_placeholder("<channel-name>, <channel-name>")
+				.lens(Settings::twitch_channels)
 				.expand_width(),
 		)
 		.with_child(
@@ -83,7 +46,58 @@ pub fn widget() -> impl Widget<UIState> {
 				.with_formatter(ParseFormatter::default())
 				.lens(Settings::chat_buffer)
 				.expand_width(),
-		);
+		)
+		.with_child(
+			TextBox::new()
+				.with_formatter(ParseFormatter::default())
+				.lens(Settings::session_timeout)
+				.expand_width(),
+		)
+		.with_child(Checkbox::new("Store in database").lens(Settings::store_history).expand_width())
+		.with_child(Checkbox::new("Record to file").lens(Settings::record_chat).expand_width())
+		.with_child(
+			TextBox::new()
+				.with_placeholder("<live chat>")
+				.lens(Settings::replay_file)
+				.expand_width(),
+		)
+		.with_child(
+			TextBox::new()
+				.with_formatter(ParseFormatter::default())
+				.lens(Settings::replay_speed)
+				.expand_width(),
+		)
+		.with_child(
+			TextBox::new()
+				.with_placeholder("<word>, <word>")
+				.lens(Settings::stop_words)
+				.expand_width(),
+		)
+		.with_child(
+			TextBox::new()
+				.with_formatter(ParseFormatter::default())
+				.lens(Settings::min_term_length)
+				.expand_width(),
+		)
+		.with_child(
+			TextBox::new()
+				.with_formatter(ParseFormatter::default())
+				.lens(Settings::spike_factor)
+				.expand_width(),
+		)
+		.with_child(
+			TextBox::new().with_placeholder("<twitch>").lens(Settings::irc_server).expand_width(),
+		)
+		.with_child(
+			TextBox::new()
+				.with_placeholder("<anonymous>")
+				.lens(Settings::login_name)
+				.expand_width(),
+		)
+		.with_child(secret_box().lens(Settings::oauth_token))
+		.with_child(secret_box().lens(Settings::refresh_token))
+		.with_child(TextBox::new().lens(Settings::client_id).expand_width())
+		.with_child(secret_box().lens(Settings::client_secret));
 
 	let columns = Flex::row().with_child(labels).with_flex_child(text_boxes, 2.0).expand_width();
 
@@ -99,12 +113,95 @@ pub fn widget() -> impl Widget<UIState> {
 		.lens(UIState::settings)
 }
 
+/// Input for entering a secret that is stored in the keyring. The secret is
+/// masked, so that it is not readable on screen while typing.
+fn secret_box() -> impl Widget<String> {
+	Label::dynamic(|secret: &String, _env| {
+		if secret.is_empty() {
+			"<stored in keyring, enter to replace>".to_owned()
+		} else {
+			"\u{2022}".repeat(secret.chars().count())
+		}
+	})
+	.controller(SecretInput)
+	.padding((4.0, 2.0))
+	.expand_width()
+	.border(Color::GRAY, 1.0)
+}
+
 /// On click of the settings save button.
 fn on_save(ctx: &mut EventCtx, data: &mut Settings, _env: &Env) {
-	data.changes = false;
-	data.save().expect("saving settings");
-	ctx.submit_command(Command::new(SETTINGS_UPDATE, (), Target::Auto));
-	tracing::debug!("Settings were saved!");
+	match data.save() {
+		Ok(()) => {
+			data.changes = false;
+			ctx.submit_command(Command::new(SETTINGS_UPDATE, (), Target::Auto));
+			tracing::debug!("Settings were saved!");
+		}
+		Err(err) => tracing::error!("Saving settings failed: {err}"),
+	}
+}
+
+/// Controller editing the secret text by keyboard input and pasting, while
+/// only the masked text is displayed.
+#[derive(Debug)]
+struct SecretInput;
+
+impl<W: Widget<String>> Controller<String, W> for SecretInput {
+	fn event(
+		&mut self,
+		child: &mut W,
+		ctx: &mut EventCtx,
+		event: &druid::Event,
+		data: &mut String,
+		env: &Env,
+	) {
+		match event {
+			druid::Event::MouseDown(_) => ctx.request_focus(),
+			druid::Event::KeyDown(key) if ctx.is_focused() => {
+				if HotKey::new(SysMods::Cmd, "v").matches(key) {
+					if let Some(text) = Application::global().clipboard().get_string() {
+						data.push_str(text.trim());
+					}
+				} else {
+					match &key.key {
+						KbKey::Character(text) if !key.mods.ctrl() && !key.mods.meta() => {
+							data.push_str(text);
+						}
+						KbKey::Backspace => {
+							data.pop();
+						}
+						KbKey::Escape => data.clear(),
+						_ => {}
+					}
+				}
+				ctx.set_handled();
+			}
+			druid::Event::Paste(clipboard) if ctx.is_focused() => {
+				if let Some(text) = clipboard.get_string() {
+					data.push_str(text.trim());
+				}
+				ctx.set_handled();
+			}
+			_ => {}
+		}
+
+		child.event(ctx, event, data, env);
+	}
+
+	fn lifecycle(
+		&mut self,
+		child: &mut W,
+		ctx: &mut LifeCycleCtx,
+		event: &LifeCycle,
+		data: &String,
+		env: &Env,
+	) {
+		if let LifeCycle::BuildFocusChain = event {
+			ctx.register_for_focus();
+		}
+
+		child.lifecycle(ctx, event, data, env);
+	}
 }
 
 /// Controller for detecting changes of the settings-values.